@@ -28,10 +28,18 @@ enum Commands {
 
 
 fn main() -> Result<(),String> {
-    match  universum::run() {
-        Commands::Cmd1(a1) => println!("Cmd1: {:?}",a1),
-        Commands::Cmd2(a2) => println!("Cmd2: {:?}",a2),
-        Commands::Cmd3 => println!("Cmd3"),
+    let cmd = match universum::run() {
+        Ok(cmd) => cmd,
+        Err(e) => {
+            eprintln!("Error: {}",e);
+            std::process::exit(e.exit_code());
+        },
+    };
+    match cmd {
+        Some(Commands::Cmd1(a1)) => println!("Cmd1: {:?}",a1),
+        Some(Commands::Cmd2(a2)) => println!("Cmd2: {:?}",a2),
+        Some(Commands::Cmd3) => println!("Cmd3"),
+        None => {},
     }
     Ok(())
 }
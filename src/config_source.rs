@@ -0,0 +1,91 @@
+use crate::topology::{RunConf, TopologyNode};
+use config::{ConfigError, Map, Source, Value, ValueKind};
+
+// `config::Source` exposing node params, so a node can be configured with:
+//   config::Config::builder()
+//       .add_source(ParamsSource::new(&node))
+//       .add_source(config::Environment::with_prefix("APP"))
+//       .build()
+// values carry the node name as their origin
+#[derive(Debug,Clone)]
+pub struct ParamsSource {
+    node: String,
+    params: serde_json::Value,
+}
+
+impl ParamsSource {
+    // nodes without params (Passive, None) give an empty source
+    pub fn new(node: &TopologyNode) -> ParamsSource {
+        ParamsSource {
+            node: node.name.clone().unwrap_or_default(),
+            params: match &node.config {
+                RunConf::Active{ params, .. } => params.clone(),
+                RunConf::Passive{ .. } |
+                RunConf::None => serde_json::Value::Object(serde_json::Map::new()),
+            },
+        }
+    }
+}
+
+fn json_into_config(origin: &String, v: &serde_json::Value) -> Value {
+    let kind = match v {
+        serde_json::Value::Null => ValueKind::Nil,
+        serde_json::Value::Bool(b) => ValueKind::Boolean(*b),
+        serde_json::Value::Number(n) => match (n.as_i64(),n.as_u64(),n.as_f64()) {
+            (Some(i),_,_) => ValueKind::I64(i),
+            (None,Some(u),_) => ValueKind::U64(u),
+            (None,None,Some(f)) => ValueKind::Float(f),
+            (None,None,None) => ValueKind::Nil,
+        },
+        serde_json::Value::String(s) => ValueKind::String(s.clone()),
+        serde_json::Value::Array(vs) => ValueKind::Array(vs.iter().map(|v| json_into_config(origin,v)).collect()),
+        serde_json::Value::Object(mv) => ValueKind::Table(mv.iter().map(|(k,v)| (k.clone(),json_into_config(origin,v))).collect()),
+    };
+    Value::new(Some(origin),kind)
+}
+
+impl Source for ParamsSource {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String,Value>,ConfigError> {
+        match &self.params {
+            serde_json::Value::Object(mv) => Ok(mv.iter()
+                .map(|(k,v)| (k.clone(),json_into_config(&self.node,v)))
+                .collect()),
+            v => Err(ConfigError::Message(format!("{}: params is not a table: {}",self.node,v))),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::{tests::example, Topology, TopologyNodeType};
+
+    #[test]
+    fn params_source() {
+        #[derive(Debug,serde::Deserialize,PartialEq)]
+        struct Settings {
+            mode: String,
+            cache: bool,
+            data: Vec<String>,
+        }
+
+        let t: Topology = example().parse().unwrap();
+        let TopologyNodeType::Node(v) = &t.root.node_type else { unreachable!() };
+        let TopologyNodeType::Node(r1) = &v[0].node_type else { unreachable!() };
+        let settings: Settings = config::Config::builder()
+            .add_source(ParamsSource::new(&r1[1]))
+            .set_override("cache",false).unwrap()
+            .build().unwrap()
+            .try_deserialize().unwrap();
+        assert_eq!(settings,Settings {
+            mode: "s".to_string(),
+            cache: false,
+            data: vec!["data2".to_string(),"data3".to_string()],
+        });
+    }
+}
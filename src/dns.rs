@@ -0,0 +1,78 @@
+use crate::topology::{ErrorKind, ParseError, RunConf, Topology};
+use std::net::IpAddr;
+
+// BIND zone records mapping logical node names to their hosts:
+//   node `r2.s.s-1` on host alias `r2` (host = "r2.local") becomes
+//   s-1.s.r2.<domain>. IN CNAME r2.local.
+// IP literal hosts get A/AAAA records instead of CNAME.
+// Only records are produced (for $INCLUDE), SOA/NS stay in the including zone.
+pub fn zone(topology: &Topology, domain: &str) -> Result<String,ParseError> {
+    let domain = domain.trim_end_matches('.');
+    let mut res = format!("$ORIGIN {}.\n",domain);
+    for node in topology.root.iter() {
+        let (name,location) = match (&node.name,&node.config) {
+            (Some(name),RunConf::Active{ location, .. }) |
+            (Some(name),RunConf::Passive{ location, .. }) => (name,location),
+            _ => continue,
+        };
+        let err = |kind: ErrorKind, error: String| ParseError {
+            kind,
+            parent: node.parent.clone().unwrap_or_default(),
+            name: name.clone(),
+            error,
+        };
+        let labels = name.split('.').rev().collect::<Vec<_>>();
+        for label in &labels {
+            let valid = !label.is_empty() && label.len() <= 63
+                && !label.starts_with('-') && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+            if !valid {
+                return Err(err(ErrorKind::InvalidName,format!("invalid dns label: {}",label)));
+            }
+        }
+        let host = match topology.hosts.get(&location.host) {
+            Some(host) => &host.host,
+            None => return Err(err(ErrorKind::UnknownHost,format!("unknown host: {}",location.host))),
+        };
+        let record = match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => format!("A\t{}",ip),
+            Ok(IpAddr::V6(ip)) => format!("AAAA\t{}",ip),
+            Err(_) => format!("CNAME\t{}.",host.trim_end_matches('.')),
+        };
+        res += &format!("{}\tIN\t{}\n",labels.join("."),record);
+    }
+    Ok(res)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::{tests, TopologyNodeType};
+
+    fn example() -> Topology {
+        tests::example()
+            .replace("r1 = { host = \"r1.local\"","r1 = { host = \"10.0.0.1\"")
+            .parse().unwrap()
+    }
+
+    #[test]
+    fn dns_zone() {
+        assert_eq!(zone(&example(),"cluster.internal.").unwrap(),"$ORIGIN cluster.internal.
+r1\tIN\tA\t10.0.0.1
+d-a.r1\tIN\tA\t10.0.0.1
+s-2.r1\tIN\tA\t10.0.0.1
+d.r2\tIN\tCNAME\tr2.local.
+s.r2\tIN\tCNAME\tr2.local.
+s-1.s.r2\tIN\tCNAME\tr2.local.
+s-2.s.r2\tIN\tCNAME\tr2.local.
+s-3.s.r2\tIN\tCNAME\tr2.local.
+");
+
+        let mut t = example();
+        if let TopologyNodeType::Node(v) = &mut t.root.node_type {
+            v[0].name = Some("r_1".to_string());
+        }
+        assert!(zone(&t,"cluster.internal").is_err());
+    }
+}
@@ -2,10 +2,17 @@ pub use clap;
 
 use clap::{Parser, Subcommand};
 use std::{
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
+use topology::{ErrorKind, LoadError, ParamsPatch, ParseError, Publicity, Topology, TopologyOptions};
 
 pub mod topology;
+pub mod selector;
+pub mod shard;
+pub mod dns;
+pub mod lint;
+#[cfg(feature = "config")]
+pub mod config_source;
 
 
 
@@ -19,6 +26,7 @@ where T: Subcommand
 }
 
 #[derive(Subcommand)]
+#[allow(clippy::enum_variant_names)]
 enum Commands<T>
 where T: Subcommand
 {
@@ -29,24 +37,159 @@ where T: Subcommand
     AppSubCommands(T),
 }
 
+// `topograf <TOPOLOGY>` validates the file, built-in tools are its subcommands
+//   so they don't take names from the application commands
 #[derive(Debug,Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct TopoConf {
+    #[command(subcommand)]
+    command: Option<TopoCommands>,
+
+    /// Topology file (TOML)
+    #[arg(required = true)]
+    topology: Option<PathBuf>,
+}
+
+#[derive(Debug,Subcommand)]
+enum TopoCommands {
+    /// Show nodes placed on every host
+    Hosts(HostsConf),
+    /// Print node params after the patch and re-validation; the file is not modified
+    Patch(PatchConf),
+}
+
+#[derive(Debug,Parser)]
+struct HostsConf {
+    /// Topology file (TOML)
+    topology: PathBuf,
+}
+
+#[derive(Debug,Parser)]
+struct PatchConf {
+    /// Dotted node name, e.g. r2.s.s-1
+    #[arg(long)]
+    node: String,
+    /// Patch is a JSON Patch (RFC 6902) operation array instead of a JSON Merge Patch (RFC 7386)
     #[arg(long)]
-    host: String,
-    #[arg(short,long,value_name="TMP_DIR")]
-    tmp: PathBuf,
+    json_patch: bool,
+    /// Topology file (TOML)
+    topology: PathBuf,
+    /// Patch document (JSON)
+    #[arg(value_parser = parse_json)]
+    patch: serde_json::Value,
+}
+
+fn parse_json(s: &str) -> Result<serde_json::Value,serde_json::Error> {
+    serde_json::from_str(s)
+}
+
+
+#[derive(Debug)]
+pub enum UniversumError {
+    Topology(LoadError),
+    Patch(ParseError),
+}
+impl UniversumError {
+    // process exit code, following sysexits.h
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            UniversumError::Topology(LoadError::Io{ .. }) => 66,    // EX_NOINPUT
+            UniversumError::Topology(LoadError::Parse{ .. }) |
+            UniversumError::Topology(LoadError::Invalid{ .. }) |
+            UniversumError::Patch(..) => 65,                        // EX_DATAERR
+        }
+    }
+}
+impl std::fmt::Display for UniversumError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UniversumError::Topology(e @ LoadError::Io{ .. }) => write!(f, "can't read topology: {}", e),
+            UniversumError::Topology(e) => write!(f, "invalid topology: {}", e),
+            UniversumError::Patch(e) => write!(f, "invalid patch: {}", e),
+        }
+    }
+}
+impl std::error::Error for UniversumError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UniversumError::Topology(e) => Some(e),
+            UniversumError::Patch(e) => Some(e),
+        }
+    }
+}
+
+// conversion warnings don't fail the command, they go to stderr
+fn load(path: &Path) -> Result<Topology,UniversumError> {
+    let (t,warnings) = Topology::from_file_with(path,&TopologyOptions::default()).map_err(UniversumError::Topology)?;
+    for w in warnings {
+        eprintln!("{}: warning: {}", path.display(), w);
+    }
+    Ok(t)
+}
+
+fn topograf(conf: TopoConf) -> Result<(),UniversumError> {
+    match (conf.command,conf.topology) {
+        (Some(TopoCommands::Hosts(conf)),_) => hosts(conf),
+        (Some(TopoCommands::Patch(conf)),_) => patch(conf),
+        (None,Some(path)) => {
+            let t = load(&path)?;
+            println!("{}: topology is valid, {} hosts", path.display(), t.hosts.len());
+            Ok(())
+        },
+        (None,None) => unreachable!("topology is required without a subcommand"),
+    }
 }
 
+fn hosts(conf: HostsConf) -> Result<(),UniversumError> {
+    let t = load(&conf.topology)?;
+    for report in t.hosts_report() {
+        match &report.host.platform {
+            Some(p) => println!("{} ({}:{}, {}/{})", report.alias, report.host.host, report.host.port, p.os, p.arch),
+            None => println!("{} ({}:{})", report.alias, report.host.host, report.host.port),
+        }
+        for n in report.nodes {
+            let publicity = match n.publicity {
+                None => "-",
+                Some(Publicity::Local) => "local",
+                Some(Publicity::Internal) => "internal",
+                Some(Publicity::External) => "external",
+            };
+            let mode = match n.active {
+                true => "active",
+                false => "passive",
+            };
+            println!("  {:<6} {:<8} {:<8} {}", n.port, publicity, mode, n.name);
+        }
+    }
+    Ok(())
+}
 
-pub fn run<T>() -> T
+fn patch(conf: PatchConf) -> Result<(),UniversumError> {
+    let mut t = load(&conf.topology)?;
+    let patch = match conf.json_patch {
+        true => ParamsPatch::Json(serde_json::from_value(conf.patch).map_err(|e| UniversumError::Patch(ParseError {
+            kind: ErrorKind::UnexpectedValue,
+            parent: String::new(),
+            name: conf.node.clone(),
+            error: e.to_string(),
+        }))?),
+        false => ParamsPatch::Merge(conf.patch),
+    };
+    t.patch_params(&conf.node,&patch).map_err(UniversumError::Patch)?;
+    if let Some(params) = t.get(&conf.node).and_then(|n| n.config.params()) {
+        println!("{}", params);
+    }
+    Ok(())
+}
+
+// Some(app_command) for the application to execute,
+//   None if a built-in command was executed by universum
+pub fn run<T>() -> Result<Option<T>,UniversumError>
 where T: Subcommand
 {
     let app = App::parse();
     match app.command {
-        Commands::Topograf(conf) => {
-            panic!("EXEC: topograf {:?}",conf);
-        },
-        Commands::AppSubCommands(t) => t,
+        Commands::Topograf(conf) => topograf(conf).map(|()| None),
+        Commands::AppSubCommands(t) => Ok(Some(t)),
     }
 }
-
@@ -0,0 +1,142 @@
+use crate::topology::{RunConf, Topology};
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,PartialOrd,Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug,PartialEq)]
+pub struct Lint {
+    pub severity: Severity,
+    pub node: Option<String>, // None for per-host totals
+    pub host: String, // host alias from topology.hosts
+    pub port: Option<u16>,
+    pub message: String,
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct PortRules {
+    // ports the OS hands out for outgoing connections
+    pub ephemeral: RangeInclusive<u16>,
+    // port -> service name
+    pub well_known: BTreeMap<u16,String>,
+}
+impl Default for PortRules {
+    fn default() -> PortRules {
+        PortRules {
+            // linux net.ipv4.ip_local_port_range
+            ephemeral: 32768 ..= 60999,
+            well_known: [
+                (22,"ssh"), (25,"smtp"), (53,"dns"), (80,"http"), (443,"https"),
+                (2379,"etcd"), (3306,"mysql"), (5432,"postgresql"), (6379,"redis"),
+                (8080,"http-alt"), (9090,"prometheus"), (9100,"node-exporter"), (27017,"mongodb"),
+            ].into_iter()
+                .map(|(port,name)| (port,name.to_string()))
+                .collect(),
+        }
+    }
+}
+
+// lints for every location:
+//   ports below 1024 without `privileged = true` (error),
+//   ports in the ephemeral range (warning),
+//   ports of well-known services (warning)
+// and for every host:
+//   more listening ports than fit outside the ephemeral range (warning),
+//   listening ports taking the whole ephemeral range (error)
+pub fn ports(topology: &Topology, rules: &PortRules) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let mut hosts: BTreeMap<&str,Vec<u16>> = BTreeMap::new();
+    for n in topology.root.iter() {
+        let (name,location) = match (&n.name,&n.config) {
+            (Some(name),RunConf::Active{ location, .. }) |
+            (Some(name),RunConf::Passive{ location }) => (name,location),
+            _ => continue,
+        };
+        hosts.entry(&location.host).or_default().push(location.port);
+        let mut lint = |severity: Severity, message: String| lints.push(Lint {
+            severity,
+            node: Some(name.clone()),
+            host: location.host.clone(),
+            port: Some(location.port),
+            message,
+        });
+        if location.port < 1024 && !location.privileged {
+            lint(Severity::Error,"privileged port without 'privileged' marker".to_string());
+        }
+        if rules.ephemeral.contains(&location.port) {
+            lint(Severity::Warning,format!("port is in the ephemeral range {}-{}",rules.ephemeral.start(),rules.ephemeral.end()));
+        }
+        if let Some(service) = rules.well_known.get(&location.port) {
+            lint(Severity::Warning,format!("port of well-known service: {}",service));
+        }
+    }
+    let range = rules.ephemeral.len();
+    let outside = u16::MAX as usize - range;
+    for (host,ports) in hosts {
+        let mut lint = |severity: Severity, message: String| lints.push(Lint {
+            severity,
+            node: None,
+            host: host.to_string(),
+            port: None,
+            message,
+        });
+        if ports.len() > outside {
+            lint(Severity::Warning,format!("{} listening ports, only {} outside the ephemeral range",ports.len(),outside));
+        }
+        let inside = ports.iter().filter(|port| rules.ephemeral.contains(port)).count();
+        if range > 0 && inside >= range {
+            lint(Severity::Error,format!("{} listening ports take the whole ephemeral range {}-{}",inside,rules.ephemeral.start(),rules.ephemeral.end()));
+        }
+    }
+    lints
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::tests::example;
+
+    #[test]
+    fn port_lints() {
+        let t: Topology = example()
+            .replace("host = \"r1\", port = 25101","host = \"r1\", port = 80")
+            .replace("host = \"r1\", port = 25102 }","host = \"r1\", port = 443, privileged = true }")
+            .replace("port = 25200","port = 40000")
+            .parse().unwrap();
+
+        let lints = ports(&t,&PortRules::default());
+        assert_eq!(lints.iter().map(|l| (l.severity,l.node.as_deref(),l.port)).collect::<Vec<_>>(),vec![
+            (Severity::Error,Some("r1.d-a"),Some(80)),
+            (Severity::Warning,Some("r1.d-a"),Some(80)),
+            (Severity::Warning,Some("r1.s-2"),Some(443)),
+            (Severity::Warning,Some("r2.d"),Some(40000)),
+        ]);
+
+        let rules = PortRules {
+            ephemeral: 49152 ..= 65535,
+            well_known: BTreeMap::new(),
+        };
+        assert_eq!(ports(&t,&rules).len(),1);
+    }
+
+    #[test]
+    fn host_totals() {
+        let t: Topology = example().parse().unwrap();
+        let totals = |ephemeral| ports(&t,&PortRules{ ephemeral, well_known: BTreeMap::new() })
+            .into_iter()
+            .filter(|l| l.node.is_none())
+            .map(|l| (l.severity,l.host))
+            .collect::<Vec<_>>();
+
+        assert_eq!(totals(32768 ..= 60999),vec![]);
+        // r1 listens on 3 ports, r2 on 5: only 4 ports are left outside the range
+        assert_eq!(totals(5 ..= 65535),vec![(Severity::Warning,"r2".to_string())]);
+        // r1 takes all of 25100-25102
+        assert_eq!(totals(25100 ..= 25102),vec![(Severity::Error,"r1".to_string())]);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::topology::{Publicity, RunConf, Topology, TopologyNode};
+
+// Node selector, parsed from strings like:
+//   host:r1
+//   node:r2.s.*
+//   publicity:external
+//   host:r2 and publicity:local or node:r1
+// `and` binds tighter than `or`
+#[derive(Debug,PartialEq)]
+pub enum Selector {
+    Host(String),      // host alias from topology.hosts
+    Node(String),      // dotted node path, '*' matches any substring
+    Publicity(Publicity),
+    And(Box<Selector>,Box<Selector>),
+    Or(Box<Selector>,Box<Selector>),
+}
+
+#[derive(Debug)]
+pub struct SelectorError {
+    pub selector: String,
+    pub error: String,
+}
+impl std::fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "selector '{}': {}", self.selector, self.error)
+    }
+}
+impl std::error::Error for SelectorError {}
+
+fn parse_term(s: &str, term: &str) -> Result<Selector,SelectorError> {
+    let err = |error: String| SelectorError {
+        selector: s.to_string(),
+        error,
+    };
+    let (kind,value) = term.split_once(':').ok_or_else(|| err(format!("expected 'kind:value', got: {}",term)))?;
+    if value.is_empty() {
+        return Err(err(format!("empty value: {}",term)));
+    }
+    match kind {
+        "host" => Ok(Selector::Host(value.to_string())),
+        "node" => Ok(Selector::Node(value.to_string())),
+        "publicity" => Ok(Selector::Publicity(match value {
+            "local" => Publicity::Local,
+            "internal" => Publicity::Internal,
+            "external" => Publicity::External,
+            _ => return Err(err(format!("unknown publicity: {}",value))),
+        })),
+        _ => Err(err(format!("unknown selector kind: {}",kind))),
+    }
+}
+
+impl std::str::FromStr for Selector {
+    type Err = SelectorError;
+    fn from_str(s: &str) -> Result<Selector,SelectorError> {
+        let err = |error: &str| SelectorError {
+            selector: s.to_string(),
+            error: error.to_string(),
+        };
+        let mut alternatives: Vec<Selector> = Vec::new();
+        let mut current: Option<Selector> = None;
+        let mut expect_term = true;
+        for token in s.split_whitespace() {
+            match (token,expect_term) {
+                ("and",false) => expect_term = true,
+                ("or",false) => {
+                    alternatives.extend(current.take());
+                    expect_term = true;
+                },
+                ("and",true) | ("or",true) => return Err(err("operator without left operand")),
+                (_,false) => return Err(err("missing 'and'/'or' between terms")),
+                (term,true) => {
+                    let sel = parse_term(s,term)?;
+                    current = Some(match current.take() {
+                        None => sel,
+                        Some(left) => Selector::And(Box::new(left),Box::new(sel)),
+                    });
+                    expect_term = false;
+                },
+            }
+        }
+        if expect_term {
+            return Err(err(match alternatives.is_empty() && current.is_none() {
+                true => "empty selector",
+                false => "operator without right operand",
+            }));
+        }
+        alternatives.extend(current);
+        let mut iter = alternatives.into_iter();
+        let first = iter.next().ok_or_else(|| err("empty selector"))?;
+        Ok(iter.fold(first,|left,right| Selector::Or(Box::new(left),Box::new(right))))
+    }
+}
+
+fn glob_match(pattern: &str, s: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == s,
+        Some((prefix,rest)) => match s.strip_prefix(prefix) {
+            None => false,
+            Some(s) => (0 ..= s.len())
+                .filter(|i| s.is_char_boundary(*i))
+                .any(|i| glob_match(rest,&s[i ..])),
+        },
+    }
+}
+
+impl Selector {
+    pub fn matches(&self, node: &TopologyNode) -> bool {
+        let location = match &node.config {
+            RunConf::Active{ location, .. } |
+            RunConf::Passive{ location, .. } => Some(location),
+            RunConf::None => None,
+        };
+        match self {
+            Selector::Host(host) => location.map(|l| &l.host == host).unwrap_or(false),
+            Selector::Node(pattern) => node.name.as_ref().map(|n| glob_match(pattern,n)).unwrap_or(false),
+            Selector::Publicity(p) => location.map(|l| l.publicity.as_ref() == Some(p)).unwrap_or(false),
+            Selector::And(a,b) => a.matches(node) && b.matches(node),
+            Selector::Or(a,b) => a.matches(node) || b.matches(node),
+        }
+    }
+
+    pub fn select<'t>(&self, topology: &'t Topology) -> Vec<&'t TopologyNode> {
+        topology.root.iter()
+            .filter(|n| self.matches(n))
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::tests::example;
+
+    fn names(sel: &str) -> Vec<String> {
+        let sel: Selector = sel.parse().unwrap();
+        sel.select(&toml::from_str(example()).unwrap())
+            .into_iter()
+            .filter_map(|n| n.name.clone())
+            .collect()
+    }
+    #[test]
+    fn selector_parse() {
+        let sel: Selector = "host:r1 or host:r2 and publicity:local".parse().unwrap();
+        assert_eq!(sel,Selector::Or(
+            Box::new(Selector::Host("r1".to_string())),
+            Box::new(Selector::And(
+                Box::new(Selector::Host("r2".to_string())),
+                Box::new(Selector::Publicity(Publicity::Local)),
+            )),
+        ));
+
+        assert!("".parse::<Selector>().is_err());
+        assert!("host:r1 and".parse::<Selector>().is_err());
+        assert!("host:r1 node:r1".parse::<Selector>().is_err());
+        assert!("label:region=eu".parse::<Selector>().is_err());
+        assert!("publicity:public".parse::<Selector>().is_err());
+        assert_eq!("publicity:public".parse::<Selector>().unwrap_err().to_string(),"selector 'publicity:public': unknown publicity: public");
+    }
+
+    #[test]
+    fn selector_select() {
+        assert_eq!(names("host:r1"),vec!["r1","r1.d-a","r1.s-2"]);
+        assert_eq!(names("node:r2.s.*"),vec!["r2.s.s-1","r2.s.s-2","r2.s.s-3"]);
+        assert_eq!(names("publicity:local and node:*-2"),vec!["r2.s.s-2"]);
+        assert_eq!(names("publicity:internal or node:r1.d-a"),vec!["r1","r1.d-a","r2.d","r2.s"]);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::topology::{ErrorKind, ParseError, RunConf, TopologyNode, TopologyNodeType};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Routes data items to the child node owning them,
+//   ownership is declared by children params (`params.data` by default):
+//
+//   [root.r2]
+//   s = ["s-1", "s-2"]
+//
+//   [config.r2.s.s-1]
+//   params = { data = [ "data1" ] }
+#[derive(Debug,PartialEq)]
+pub struct ShardRouter {
+    // data item -> owning node name
+    owners: BTreeMap<String,String>,
+}
+
+// default extractor: `params.data` array of strings
+pub fn data_items(params: &serde_json::Value) -> Result<Vec<String>,String> {
+    pointer_items(params,"/data")
+}
+
+// array of strings at JSON pointer `pointer` (e.g. "/data") inside params
+pub fn pointer_items(params: &serde_json::Value, pointer: &str) -> Result<Vec<String>,String> {
+    match params.pointer(pointer) {
+        None => Err(format!("params '{}' is missed",pointer)),
+        Some(serde_json::Value::Array(vs)) => vs.iter()
+            .map(|v| match v {
+                serde_json::Value::String(s) => Ok(s.clone()),
+                _ => Err(format!("unexpected data item: {}",v)),
+            })
+            .collect(),
+        Some(v) => Err(format!("unexpected params '{}': {}",pointer,v)),
+    }
+}
+
+// result of matching sibling shards against the declared full set of items
+#[derive(Debug,Default,PartialEq)]
+pub struct Coverage {
+    // declared, but not owned by any shard
+    pub missing: BTreeSet<String>,
+    // owned, but not declared: item -> owning nodes
+    pub unexpected: BTreeMap<String,Vec<String>>,
+    // owned by more than one shard: item -> owning nodes
+    pub duplicated: BTreeMap<String,Vec<String>>,
+}
+impl Coverage {
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.unexpected.is_empty() && self.duplicated.is_empty()
+    }
+}
+
+// checks that the children of `node` own every item of `full` exactly once,
+//   items are taken from params at JSON pointer `pointer`
+pub fn check_coverage(node: &TopologyNode, pointer: &str, full: &BTreeSet<String>) -> Result<Coverage,ParseError> {
+    let parent = node.name.clone().unwrap_or_default();
+    let mut owners: BTreeMap<String,Vec<String>> = BTreeMap::new();
+    if let TopologyNodeType::Node(children) = &node.node_type {
+        for child in children {
+            let name = child.name.clone().unwrap_or_default();
+            let items = match &child.config {
+                RunConf::Active{ params, .. } => pointer_items(params,pointer),
+                _ => Err("shard without params".to_string()),
+            }.map_err(|error| ParseError {
+                kind: ErrorKind::InvalidData,
+                parent: parent.clone(),
+                name: name.clone(),
+                error,
+            })?;
+            for item in items {
+                owners.entry(item).or_default().push(name.clone());
+            }
+        }
+    }
+    let mut coverage = Coverage {
+        missing: full.iter().filter(|item| !owners.contains_key(*item)).cloned().collect(),
+        ..Coverage::default()
+    };
+    for (item,nodes) in owners {
+        if nodes.len() > 1 {
+            coverage.duplicated.insert(item.clone(),nodes.clone());
+        }
+        if !full.contains(&item) {
+            coverage.unexpected.insert(item,nodes);
+        }
+    }
+    Ok(coverage)
+}
+
+impl ShardRouter {
+    pub fn new(node: &TopologyNode) -> Result<ShardRouter,ParseError> {
+        ShardRouter::with_extractor(node,data_items)
+    }
+
+    // every child of `node` must be Active, own at least one item, and no item can be owned twice
+    pub fn with_extractor<F>(node: &TopologyNode, extractor: F) -> Result<ShardRouter,ParseError>
+    where F: Fn(&serde_json::Value) -> Result<Vec<String>,String>
+    {
+        let parent = node.name.clone().unwrap_or_default();
+        let children = match &node.node_type {
+            TopologyNodeType::Node(v) if !v.is_empty() => v,
+            _ => return Err(ParseError {
+                kind: ErrorKind::InvalidData,
+                parent: node.parent.clone().unwrap_or_default(),
+                name: parent,
+                error: "no shards".to_string(),
+            }),
+        };
+        let mut owners = BTreeMap::new();
+        for child in children {
+            let name = child.name.clone().unwrap_or_default();
+            let err = |kind: ErrorKind, error: String| ParseError {
+                kind,
+                parent: parent.clone(),
+                name: name.clone(),
+                error,
+            };
+            let items = match &child.config {
+                RunConf::Active{ params, .. } => extractor(params).map_err(|e| err(ErrorKind::InvalidData,e))?,
+                _ => return Err(err(ErrorKind::InvalidData,"shard without params".to_string())),
+            };
+            if items.is_empty() {
+                return Err(err(ErrorKind::InvalidData,"shard owns no data".to_string()));
+            }
+            for item in items {
+                if let Some(owner) = owners.insert(item.clone(),name.clone()) {
+                    return Err(err(ErrorKind::DuplicateData,format!("data '{}' is already owned by {}",item,owner)));
+                }
+            }
+        }
+        Ok(ShardRouter{ owners })
+    }
+
+    // name of the node owning `item`
+    pub fn route(&self, item: &str) -> Option<&str> {
+        self.owners.get(item).map(String::as_str)
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = (&str,&str)> {
+        self.owners.iter().map(|(item,owner)| (item.as_str(),owner.as_str()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::{tests::example, Topology};
+
+    // r2.s with `data` of its shards s-1, s-2, s-3 replaced
+    fn shards(data: [&str;3]) -> TopologyNode {
+        let mut s = example().to_string();
+        for (i,d) in data.iter().enumerate() {
+            s = s.replace(
+                &format!("params = {{ mode = \"s\", data = [ \"data{}\" ] }}\nlocation = {{ host = \"r2\"",i+1),
+                &format!("params = {{ mode = \"s\", data = {} }}\nlocation = {{ host = \"r2\"",d),
+            );
+        }
+        let t: Topology = toml::from_str(&s).unwrap();
+        let TopologyNodeType::Node(mut v) = t.root.node_type else { unreachable!() };
+        v.remove(2)
+    }
+
+    #[test]
+    fn shard_router() {
+        let r = ShardRouter::new(&shards(["[\"data1\", \"data3\"]","[\"data2\"]","[\"data4\"]"])).unwrap();
+        assert_eq!(r.route("data1"),Some("r2.s.s-1"));
+        assert_eq!(r.route("data2"),Some("r2.s.s-2"));
+        assert_eq!(r.route("data3"),Some("r2.s.s-1"));
+        assert_eq!(r.route("data4"),Some("r2.s.s-3"));
+        assert_eq!(r.route("data5"),None);
+
+        assert!(ShardRouter::new(&shards(["[\"data1\"]","[\"data1\"]","[\"data3\"]"])).is_err());
+        assert!(ShardRouter::new(&shards(["[\"data1\"]","[]","[\"data3\"]"])).is_err());
+        assert!(ShardRouter::new(&shards(["[\"data1\"]","\"data2\"","[\"data3\"]"])).is_err());
+    }
+
+    #[test]
+    fn shard_coverage() {
+        let full = ["data1","data2","data3"].iter().map(|s| s.to_string()).collect();
+        let covered = || shards(["[\"data1\"]","[\"data2\"]","[\"data3\"]"]);
+
+        let c = check_coverage(&covered(),"/data",&full).unwrap();
+        assert!(c.is_ok());
+
+        let c = check_coverage(&shards(["[\"data1\", \"data4\"]","[\"data1\"]","[]"]),"/data",&full).unwrap();
+        assert!(!c.is_ok());
+        assert_eq!(c,Coverage {
+            missing: ["data2","data3"].iter().map(|s| s.to_string()).collect(),
+            unexpected: vec![("data4".to_string(),vec!["r2.s.s-1".to_string()])].into_iter().collect(),
+            duplicated: vec![("data1".to_string(),vec!["r2.s.s-1".to_string(),"r2.s.s-2".to_string()])].into_iter().collect(),
+        });
+
+        assert!(check_coverage(&covered(),"/items",&full).is_err());
+    }
+}
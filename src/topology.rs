@@ -1,5 +1,7 @@
 use serde::Deserialize;
 use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug,Deserialize,PartialEq)]
 #[serde(try_from = "TomlTopology")]
@@ -9,29 +11,43 @@ pub struct Topology {
 
     // logical software node tree
     pub root: TopologyNode,
+
+    // org-wide constraints for locations
+    pub network: NetworkPolicy,
 }
 
 #[derive(Debug,Clone,Deserialize,PartialEq)]
 pub struct Host {
     pub host: String,
     pub port: u16,
+    pub platform: Option<Platform>,
 }
 
-#[derive(Debug,PartialEq)]
+#[derive(Debug,Clone,Deserialize,PartialEq)]
+pub struct Platform {
+    pub os: String,   // e.g. "linux", "windows"
+    pub arch: String, // e.g. "x86_64", "aarch64"
+}
+
+#[derive(Debug,Clone,PartialEq)]
 pub struct TopologyNode {
     pub name: Option<String>,
     pub parent: Option<String>,
     pub config: RunConf,
+    // free-form annotations never interpreted by universum:
+    //   [config.*.meta] table entries and `x-` prefixed non-table values of [config.*] (stored with the prefix);
+    //   `meta` is reserved and can't name a nested node, `x-` tables are child configs as usual
+    pub meta: serde_json::Map<String,serde_json::Value>,
     pub node_type: TopologyNodeType,
 }
 
-#[derive(Debug,PartialEq)]
+#[derive(Debug,Clone,PartialEq)]
 pub enum TopologyNodeType {
     Terminal,
     Node(Vec<TopologyNode>),
 }
 
-#[derive(Debug,PartialEq)]
+#[derive(Debug,Clone,PartialEq)]
 pub enum RunConf {
     None,
     Active {
@@ -43,14 +59,17 @@ pub enum RunConf {
     },
 }
 
-#[derive(Debug,Deserialize,PartialEq)]
+#[derive(Debug,Clone,Deserialize,PartialEq)]
 pub struct Location {
     pub host: String, // host alias from topology.host
     pub port: u16,
     pub publicity: Option<Publicity>,
+    // service is allowed to listen on a port below 1024
+    #[serde(default)]
+    pub privileged: bool,
 }
 
-#[derive(Debug,Deserialize,PartialEq)]
+#[derive(Debug,Clone,Deserialize,PartialEq,Eq,PartialOrd,Ord)]
 #[serde(rename_all = "snake_case")]
 pub enum Publicity {
     Local,
@@ -58,6 +77,114 @@ pub enum Publicity {
     External,
 }
 
+// [network]
+// forbidden_ports = [22]
+// cidrs = { external = ["203.0.113.0/24"], internal = ["10.0.0.0/8"] }
+#[derive(Debug,Clone,Default,Deserialize,PartialEq)]
+pub struct NetworkPolicy {
+    // ports no location may use
+    #[serde(default)]
+    pub forbidden_ports: Vec<u16>,
+    // addresses allowed for hosts of locations with the given publicity,
+    //   such hosts have to be IP literals to be checked at parse time
+    #[serde(default)]
+    pub cidrs: BTreeMap<Publicity,Vec<Cidr>>,
+}
+
+#[derive(Debug,Clone,Copy,Deserialize,PartialEq)]
+#[serde(try_from = "String")]
+pub struct Cidr {
+    pub addr: IpAddr,
+    pub prefix: u8,
+}
+impl Cidr {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr,ip) {
+            // the fields are public, so an out-of-range prefix matches nothing
+            (IpAddr::V4(net),IpAddr::V4(ip)) => match 32u32.checked_sub(self.prefix as u32) {
+                Some(shift) => {
+                    let mask = u32::MAX.checked_shl(shift).unwrap_or(0);
+                    u32::from(net) & mask == u32::from(*ip) & mask
+                },
+                None => false,
+            },
+            (IpAddr::V6(net),IpAddr::V6(ip)) => match 128u32.checked_sub(self.prefix as u32) {
+                Some(shift) => {
+                    let mask = u128::MAX.checked_shl(shift).unwrap_or(0);
+                    u128::from(net) & mask == u128::from(*ip) & mask
+                },
+                None => false,
+            },
+            _ => false,
+        }
+    }
+}
+impl std::str::FromStr for Cidr {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Cidr,String> {
+        let (addr,prefix) = s.split_once('/').ok_or_else(|| format!("expected 'addr/prefix', got: {}",s))?;
+        let addr: IpAddr = addr.parse().map_err(|e| format!("{}: {}",s,e))?;
+        let prefix: u8 = prefix.parse().map_err(|e| format!("{}: {}",s,e))?;
+        let max = match addr {
+            IpAddr::V4(..) => 32,
+            IpAddr::V6(..) => 128,
+        };
+        match prefix <= max {
+            true => Ok(Cidr{ addr, prefix }),
+            false => Err(format!("{}: prefix is too long",s)),
+        }
+    }
+}
+impl TryFrom<String> for Cidr {
+    type Error = String;
+    fn try_from(s: String) -> Result<Cidr,String> {
+        s.parse()
+    }
+}
+impl std::fmt::Display for Cidr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+// parsing options, TopologyOptions::default() is what FromStr and from_file use
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct TopologyOptions {
+    pub floats: FloatConversion,
+    pub datetimes: DatetimeConversion,
+    // `${ENV_VAR}` in hosts host/port, location port and params strings, `$${` for literal `${`
+    pub env_substitution: bool,
+}
+
+// NaN and infinite floats in params (JSON has no such numbers)
+#[derive(Debug,Clone,Copy,Default,PartialEq,Eq)]
+pub enum FloatConversion {
+    #[default]
+    Null,  // converted to null with a warning
+    Error, // ErrorKind::UnexpectedValue
+}
+
+// TOML datetimes in params
+#[derive(Debug,Clone,Copy,Default,PartialEq,Eq)]
+pub enum DatetimeConversion {
+    #[default]
+    String, // RFC 3339 string, e.g. "1979-05-27T07:32:00Z"
+    Tagged, // { "$datetime": "1979-05-27T07:32:00Z" }, distinguishable from strings
+}
+
+// lossy params conversion
+#[derive(Debug,Clone,PartialEq)]
+pub struct Warning {
+    pub node: String,    // dotted node name
+    pub pointer: String, // JSON pointer inside node params, "meta/.." for meta
+    pub message: String,
+}
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} params '{}': {}", self.node, self.pointer, self.message)
+    }
+}
+
 #[derive(Debug,Deserialize,PartialEq)]
 struct TomlTopology {
     // physical host aliases
@@ -67,35 +194,187 @@ struct TomlTopology {
     root: toml::Table,
 
     config: toml::Table,
+
+    #[serde(default)]
+    network: NetworkPolicy,
 }
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum ErrorKind {
+    MissedConfig,     // node in [root] without [config.*] section
+    MissedField,      // config without 'params' or 'location'
+    InvalidLocation,
+    UnexpectedValue,
+    UnknownHost,
+    DuplicateService, // same host:port
+    DuplicateNode,
+    ConflictingHost,  // same alias, different host records
+    TerminalParent,   // node attached to a terminal node
+    InvalidName,
+    InvalidData,      // shard data missed or malformed
+    DuplicateData,    // shard data owned twice
+    NetworkPolicy,    // location violates [network]
+    UnknownNode,      // no node with such a name
+}
+
 #[derive(Debug)]
 pub struct ParseError {
-    pub parent: String,
-    pub name: String,
-    pub error: String,
+    pub(crate) kind: ErrorKind,
+    pub(crate) parent: String,
+    pub(crate) name: String,
+    pub(crate) error: String,
+}
+impl ParseError {
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+    // dotted path of the parent ("config" or "hosts" for section level errors)
+    pub fn parent(&self) -> &str {
+        &self.parent
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn message(&self) -> &str {
+        &self.error
+    }
 }
+// "config.r1.a: duplicate service (r1:25100): r1", names that are already full paths are not prefixed
 impl std::fmt::Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("ParseError")
-            .field("parent", &self.parent)
-            .field("name", &self.name)
-            .field("error", &self.error)
-            .finish()
+        let full = self.parent.is_empty() || self.name.starts_with(&format!("{}.",self.parent));
+        match full {
+            true => write!(f, "{}: {}", self.name, self.error),
+            false => write!(f, "{}.{}: {}", self.parent, self.name, self.error),
+        }
     }
 }
+impl std::error::Error for ParseError {}
 
-fn run_root(parent: &Option<String>, table: toml::Table, confs: &mut BTreeMap<String,RunConf>) -> Result<Vec<TopologyNode>,ParseError> {
+#[derive(Debug)]
+pub enum LoadError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Parse {
+        path: Option<PathBuf>,
+        // 1-based position in the source, if known
+        line: Option<usize>,
+        column: Option<usize>,
+        error: String,
+    },
+    // semantically invalid topology
+    Invalid {
+        path: Option<PathBuf>,
+        error: ParseError,
+    },
+}
+impl LoadError {
+    fn parse(path: Option<&Path>, source: &str, e: toml::de::Error) -> LoadError {
+        let (line,column) = match e.span() {
+            Some(span) if span.start <= source.len() => {
+                let before = &source[.. span.start];
+                let line = before.matches('\n').count() + 1;
+                let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+                (Some(line),Some(column))
+            },
+            _ => (None,None),
+        };
+        LoadError::Parse {
+            path: path.map(Path::to_path_buf),
+            line,
+            column,
+            error: e.message().to_string(),
+        }
+    }
+}
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Io{ path, error } => write!(f, "{}: {}", path.display(), error),
+            LoadError::Parse{ path, line, column, error } => {
+                match path {
+                    Some(path) => write!(f, "{}", path.display())?,
+                    None => write!(f, "<topology>")?,
+                }
+                if let Some(line) = line {
+                    write!(f, ":{}", line)?;
+                }
+                if let Some(column) = column {
+                    write!(f, ":{}", column)?;
+                }
+                write!(f, ": {}", error)
+            },
+            LoadError::Invalid{ path: Some(path), error } => write!(f, "{}: {}", path.display(), error),
+            LoadError::Invalid{ path: None, error } => write!(f, "<topology>: {}", error),
+        }
+    }
+}
+#[derive(Debug)]
+pub enum ParamsError {
+    NoParams,                          // Passive or None config
+    Missed(String),                    // JSON pointer
+    Invalid(String,serde_json::Error), // JSON pointer, deserialization error
+    Patch(usize,String),               // JSON Patch operation index, error
+}
+impl std::fmt::Display for ParamsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParamsError::NoParams => write!(f, "no params"),
+            ParamsError::Missed(pointer) => write!(f, "params '{}' is missed", pointer),
+            ParamsError::Invalid(pointer,e) if pointer.is_empty() => write!(f, "invalid params: {}", e),
+            ParamsError::Invalid(pointer,e) => write!(f, "invalid params '{}': {}", pointer, e),
+            ParamsError::Patch(i,e) => write!(f, "patch operation {} failed: {}", i, e),
+        }
+    }
+}
+impl std::error::Error for ParamsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParamsError::Invalid(_,e) => Some(e),
+            ParamsError::NoParams | ParamsError::Missed(..) | ParamsError::Patch(..) => None,
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io{ error, .. } => Some(error),
+            LoadError::Invalid{ error, .. } => Some(error),
+            LoadError::Parse{ .. } => None,
+        }
+    }
+}
+
+// [config.<parent>.meta] is node metadata, so nested nodes can't be named `meta`
+fn reserved_meta(parent: String, name: String) -> ParseError {
+    ParseError {
+        parent,
+        name,
+        kind: ErrorKind::InvalidName,
+        error: "'meta' is reserved for node metadata".to_string(),
+    }
+}
+
+fn run_root(parent: &Option<String>, table: toml::Table, confs: &mut BTreeMap<String,RunConf>, metas: &mut BTreeMap<String,serde_json::Map<String,serde_json::Value>>) -> Result<Vec<TopologyNode>,ParseError> {
     let mut nodes = Vec::new();
     for (name,v) in table {        
         match v {
             toml::Value::Table(t) => {
+                if let (Some(parent),"meta") = (parent,name.as_str()) {
+                    return Err(reserved_meta(parent.clone(),name));
+                }
                 let next_parent = match parent {
                     None => name,
                     Some(parent) => format!("{}.{}",parent,name),
                 };
-                nodes.extend(run_root(&Some(next_parent),t,confs)?);                    
+                nodes.extend(run_root(&Some(next_parent),t,confs,metas)?);                    
             },
             toml::Value::Array(vs) => {
+                if let (Some(parent),"meta") = (parent,name.as_str()) {
+                    return Err(reserved_meta(parent.clone(),name));
+                }
                 let mut tps = Vec::new();
                 for v in vs {
                     match v {
@@ -104,24 +383,30 @@ fn run_root(parent: &Option<String>, table: toml::Table, confs: &mut BTreeMap<St
                                 None => name.clone(),
                                 Some(parent) => format!("{}.{}",parent,name),
                             };
+                            if s == "meta" {
+                                return Err(reserved_meta(p,s));
+                            }
                             let n = format!("{}.{}",p,s);
                             tps.push(TopologyNode {
                                 config: match confs.remove(&n) {
                                     None => return Err(ParseError{
                                         parent: p,
                                         name: s,
-                                        error: format!("missed config"),
+                                        kind: ErrorKind::MissedConfig,
+                                        error: "missed config".to_string(),
                                     }),
                                     Some(conf) => conf,
                                 },
+                                meta: metas.remove(&n).unwrap_or_default(),
                                 name: Some(n),
                                 parent: Some(p),
                                 node_type: TopologyNodeType::Terminal,
                             });                           
                         },
                         _ => return Err(ParseError{
-                            parent: parent.clone().unwrap_or_else(||String::new()),
+                            parent: parent.clone().unwrap_or_default(),
                             name,
+                            kind: ErrorKind::UnexpectedValue,
                             error: format!("unexpected value: {:?}",v),
                         }),
                     }
@@ -133,152 +418,1148 @@ fn run_root(parent: &Option<String>, table: toml::Table, confs: &mut BTreeMap<St
                 nodes.push(TopologyNode {
                     config: match confs.remove(&n) {
                         None => return Err(ParseError{
-                            parent: parent.clone().unwrap_or_else(||String::new()),
+                            parent: parent.clone().unwrap_or_default(),
                             name,
-                            error: format!("missed config"),
+                            kind: ErrorKind::MissedConfig,
+                            error: "missed config".to_string(),
                         }),
                         Some(conf) => conf,
                     },
+                    meta: metas.remove(&n).unwrap_or_default(),
                     name: Some(n),
                     parent: parent.clone(),
                     node_type: TopologyNodeType::Node(tps),
                 });
             },
-            v @ _ => return Err(ParseError{
-                parent: parent.clone().unwrap_or_else(||String::new()),
+            v => return Err(ParseError{
+                parent: parent.clone().unwrap_or_default(),
                 name,
+                kind: ErrorKind::UnexpectedValue,
                 error: format!("unexpected value: {:?}",v),
             }),
         }
     }
     Ok(nodes)
 }
-fn toml_into_json(v: toml::Value) -> serde_json::Value {
+// RFC 3339 string independent of toml Display:
+//   "1979-05-27T07:32:00.5-07:00", "1979-05-27", "07:32:00"
+//   fraction without trailing zeros, zero offset as "Z", the offset is kept (no UTC conversion)
+fn datetime_string(dt: &toml::value::Datetime) -> String {
+    let mut res = String::new();
+    if let Some(d) = &dt.date {
+        res += &format!("{:04}-{:02}-{:02}",d.year,d.month,d.day);
+    }
+    if let Some(t) = &dt.time {
+        if dt.date.is_some() {
+            res.push('T');
+        }
+        res += &format!("{:02}:{:02}:{:02}",t.hour,t.minute,t.second);
+        if t.nanosecond > 0 {
+            res += format!(".{:09}",t.nanosecond).trim_end_matches('0');
+        }
+    }
+    match dt.offset {
+        None => {},
+        Some(toml::value::Offset::Z) |
+        Some(toml::value::Offset::Custom{ minutes: 0 }) => res.push('Z'),
+        Some(toml::value::Offset::Custom{ minutes }) => {
+            let sign = match minutes < 0 {
+                true => '-',
+                false => '+',
+            };
+            res += &format!("{}{:02}:{:02}",sign,minutes.unsigned_abs() / 60,minutes.unsigned_abs() % 60);
+        },
+    }
+    res
+}
+
+fn env_substitute(s: &str) -> Result<String,String> {
+    let mut res = String::new();
+    let mut rest = s;
+    while let Some(i) = rest.find("${") {
+        if rest[.. i].ends_with('$') {
+            res += &rest[.. i - 1];
+            res += "${";
+            rest = &rest[i + 2 ..];
+            continue;
+        }
+        res += &rest[.. i];
+        let (name,tail) = rest[i + 2 ..].split_once('}').ok_or_else(|| format!("unterminated '${{' in: {}",s))?;
+        res += &std::env::var(name).map_err(|e| format!("environment variable '{}': {}",name,e))?;
+        rest = tail;
+    }
+    res += rest;
+    Ok(res)
+}
+
+fn env_value(v: &mut toml::Value, path: &str) -> Result<(),String> {
     match v {
+        toml::Value::String(s) => *s = env_substitute(s).map_err(|e| format!("{}: {}",path,e))?,
+        toml::Value::Array(vs) => for (i,v) in vs.iter_mut().enumerate() {
+            env_value(v,&format!("{}.{}",path,i))?;
+        },
+        toml::Value::Table(t) => for (k,v) in t.iter_mut() {
+            env_value(v,&format!("{}.{}",path,k))?;
+        },
+        _ => {},
+    }
+    Ok(())
+}
+
+// port given as a string, e.g. port = "${PORT}", becomes an integer after substitution
+fn env_port(v: &mut toml::Value, path: &str) -> Result<(),String> {
+    if let toml::Value::String(s) = v {
+        let port = env_substitute(s).map_err(|e| format!("{}: {}",path,e))?;
+        *v = toml::Value::Integer(port.parse().map_err(|_| format!("{}: invalid port: {}",path,port))?);
+    }
+    Ok(())
+}
+
+// metadata is classified as in run_conf: nested `meta` tables and non-table `x-` values are left as is
+fn env_config(t: &mut toml::Table, path: &str) -> Result<(),String> {
+    let nested = path.contains('.');
+    for (k,v) in t.iter_mut() {
+        let path = format!("{}.{}",path,k);
+        match (k.as_str(),v) {
+            ("meta",_) if nested => {},
+            ("params",v) => env_value(v,&path)?,
+            ("location",toml::Value::Table(loc)) => if let Some(port) = loc.get_mut("port") {
+                env_port(port,&format!("{}.port",path))?;
+            },
+            (_,toml::Value::Table(t)) => env_config(t,&path)?,
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+fn env_topology(t: &mut toml::Table) -> Result<(),String> {
+    if let Some(toml::Value::Table(hosts)) = t.get_mut("hosts") {
+        for (alias,host) in hosts.iter_mut() {
+            if let toml::Value::Table(host) = host {
+                if let Some(v) = host.get_mut("host") {
+                    env_value(v,&format!("hosts.{}.host",alias))?;
+                }
+                if let Some(v) = host.get_mut("port") {
+                    env_port(v,&format!("hosts.{}.port",alias))?;
+                }
+            }
+        }
+    }
+    if let Some(toml::Value::Table(config)) = t.get_mut("config") {
+        env_config(config,"config")?;
+    }
+    Ok(())
+}
+
+// params conversion state of one node
+struct Conversion<'t> {
+    node: String,
+    options: &'t TopologyOptions,
+    warnings: &'t mut Vec<Warning>,
+}
+
+fn toml_into_json(v: toml::Value, pointer: String, conv: &mut Conversion) -> Result<serde_json::Value,String> {
+    Ok(match v {
         toml::Value::String(s) => serde_json::Value::String(s),
         toml::Value::Integer(i) => serde_json::Value::Number(i.into()),
-        toml::Value::Float(f) => match serde_json::value::Number::from_f64(f) {
-            None => serde_json::Value::Null,
-            Some(n) => serde_json::Value::Number(n),
-        }
+        toml::Value::Float(f) => match (serde_json::value::Number::from_f64(f),conv.options.floats) {
+            (Some(n),_) => serde_json::Value::Number(n),
+            (None,FloatConversion::Null) => {
+                conv.warnings.push(Warning {
+                    node: conv.node.clone(),
+                    pointer,
+                    message: format!("float {} is converted to null",f),
+                });
+                serde_json::Value::Null
+            },
+            (None,FloatConversion::Error) => return Err(format!("params '{}': float {} is not representable in JSON",pointer,f)),
+        },
         toml::Value::Boolean(b) => serde_json::Value::Bool(b),
-        toml::Value::Datetime(dt) => serde_json::Value::String(dt.to_string()),
-        toml::Value::Array(vs) => serde_json::Value::Array(vs.into_iter().map(toml_into_json).collect()),
-        toml::Value::Table(mv) => serde_json::Value::Object(mv.into_iter().map(|(s,v)|(s,toml_into_json(v))).collect()),
-    }
+        toml::Value::Datetime(dt) => match conv.options.datetimes {
+            DatetimeConversion::String => serde_json::Value::String(datetime_string(&dt)),
+            DatetimeConversion::Tagged => serde_json::json!({ "$datetime": datetime_string(&dt) }),
+        },
+        toml::Value::Array(vs) => serde_json::Value::Array(vs.into_iter()
+            .enumerate()
+            .map(|(i,v)| toml_into_json(v,format!("{}/{}",pointer,i),conv))
+            .collect::<Result<_,_>>()?),
+        toml::Value::Table(mv) => serde_json::Value::Object(mv.into_iter()
+            .map(|(k,v)| {
+                let pointer = format!("{}/{}",pointer,k.replace('~',"~0").replace('/',"~1"));
+                toml_into_json(v,pointer,conv).map(|v| (k,v))
+            })
+            .collect::<Result<_,_>>()?),
+    })
+}
+// node configs go to `map`, their meta to `metas`
+struct Configs<'t> {
+    map: &'t mut BTreeMap<String,RunConf>,
+    metas: &'t mut BTreeMap<String,serde_json::Map<String,serde_json::Value>>,
+    options: &'t TopologyOptions,
+    warnings: &'t mut Vec<Warning>,
 }
-fn run_conf(parent: &Option<String>, table: toml::Table, map: &mut BTreeMap<String,RunConf>) -> Result<(),ParseError> {
+
+fn run_conf(parent: &Option<String>, table: toml::Table, cfg: &mut Configs) -> Result<(),ParseError> {
     for (name,v) in table {
         match v {
             toml::Value::Table(mut t) => {
+                let node = match parent {
+                    None => name.clone(),
+                    Some(parent) => format!("{}.{}",parent,name),
+                };
+                let mut conv = Conversion {
+                    node,
+                    options: cfg.options,
+                    warnings: &mut *cfg.warnings,
+                };
+                let mut meta = serde_json::Map::new();
+                let mut meta_values = Vec::new();
+                match t.remove("meta") {
+                    Some(toml::Value::Table(m)) => meta_values.extend(m),
+                    Some(v) => return Err(ParseError{
+                        parent: parent.clone().unwrap_or_default(),
+                        name,
+                        kind: ErrorKind::UnexpectedValue,
+                        error: format!("meta is not a table: {:?}",v),
+                    }),
+                    None => {},
+                }
+                let keys = t.iter()
+                    .filter(|(k,v)| k.starts_with("x-") && !v.is_table())
+                    .map(|(k,_)| k.clone())
+                    .collect::<Vec<_>>();
+                meta_values.extend(keys.into_iter().filter_map(|k| t.remove(&k).map(|v| (k,v))));
+                for (k,v) in meta_values {
+                    let pointer = format!("meta/{}",k.replace('~',"~0").replace('/',"~1"));
+                    let v = toml_into_json(v,pointer,&mut conv).map_err(|error| ParseError{
+                        parent: parent.clone().unwrap_or_default(),
+                        name: name.clone(),
+                        kind: ErrorKind::UnexpectedValue,
+                        error,
+                    })?;
+                    meta.insert(k,v);
+                }
                 let conf = match (t.remove("params"),t.remove("location")) {
                     (Some(ps),Some(loc)) => RunConf::Active {
-                        params: toml_into_json(ps),
+                        params: toml_into_json(ps,String::new(),&mut conv).map_err(|error| ParseError{
+                            parent: parent.clone().unwrap_or_default(),
+                            name: name.clone(),
+                            kind: ErrorKind::UnexpectedValue,
+                            error,
+                        })?,
                         location: loc.try_into().map_err(|e| ParseError{
-                            parent: parent.clone().unwrap_or_else(||String::new()),
+                            parent: parent.clone().unwrap_or_default(),
                             name: name.clone(),
+                            kind: ErrorKind::InvalidLocation,
                             error: format!("{:?}",e),
                         })?,
                     },
                     (Some(..),None) => return Err(ParseError{
-                        parent: parent.clone().unwrap_or_else(||String::new()),
+                        parent: parent.clone().unwrap_or_default(),
                         name,
-                        error: format!("conf 'location' is missed"),
+                        kind: ErrorKind::MissedField,
+                        error: "conf 'location' is missed".to_string(),
                     }),
                     (None,Some(..)) => return Err(ParseError{
-                        parent: parent.clone().unwrap_or_else(||String::new()),
+                        parent: parent.clone().unwrap_or_default(),
                         name,
-                        error: format!("conf 'params' is missed"),
+                        kind: ErrorKind::MissedField,
+                        error: "conf 'params' is missed".to_string(),
                     }),
                     _ => return Err(ParseError{
-                        parent: parent.clone().unwrap_or_else(||String::new()),
+                        parent: parent.clone().unwrap_or_default(),
                         name,
-                        error: format!("conf 'location' and 'params' are missed"),
+                        kind: ErrorKind::MissedField,
+                        error: "conf 'location' and 'params' are missed".to_string(),
                     }),
                 };
-                let next_parent = match parent {
-                    None => name,
-                    Some(parent) => format!("{}.{}",parent,name),
-                };
-                map.insert(next_parent.clone(),conf);
+                let next_parent = conv.node;
+                if !meta.is_empty() {
+                    cfg.metas.insert(next_parent.clone(),meta);
+                }
+                cfg.map.insert(next_parent.clone(),conf);
                 
-                run_conf(&Some(next_parent),t,map)?;
+                run_conf(&Some(next_parent),t,cfg)?;
+            },
+            v => return Err(ParseError{
+                parent: parent.clone().unwrap_or_default(),
+                name,
+                kind: ErrorKind::UnexpectedValue,
+                error: format!("unexpected value: {:?}",v),
+            }),
+        }
+    }
+    Ok(())
+}
+
+fn check_network(host: &Host, network: &NetworkPolicy, location: &Location) -> Result<(),String> {
+    if network.forbidden_ports.contains(&location.port) {
+        return Err(format!("forbidden port: {}",location.port));
+    }
+    let cidrs = match location.publicity.as_ref().and_then(|p| network.cidrs.get(p)) {
+        None => return Ok(()),
+        Some(cidrs) => cidrs,
+    };
+    let ip: IpAddr = host.host.parse()
+        .map_err(|_| format!("host address is not an IP, can't check allowed cidrs: {}",host.host))?;
+    match cidrs.iter().any(|c| c.contains(&ip)) {
+        true => Ok(()),
+        false => Err(format!("host address is not in allowed cidrs: {}",ip)),
+    }
+}
+
+// check that every location refers to a known host, follows the network policy,
+//   and no two services share host:port
+fn check_locations<'c,I>(hosts: &BTreeMap<String,Host>, network: &NetworkPolicy, confs: I) -> Result<(),ParseError>
+where I: IntoIterator<Item = (&'c String,&'c RunConf)>
+{
+    let mut services = BTreeMap::new();
+    for (name,c) in confs {
+        match c {
+            RunConf::Active{ location, .. } |
+            RunConf::Passive{ location, .. } => {
+                match hosts.get(&location.host) {
+                    Some(host) => {
+                        check_network(host,network,location).map_err(|error| ParseError {
+                            parent: "config".to_string(),
+                            name: name.clone(),
+                            kind: ErrorKind::NetworkPolicy,
+                            error,
+                        })?;
+                        let s = format!("{}:{}",location.host,location.port);
+                        match services.get(&s) {
+                            None => { services.insert(s,name); },
+                            Some(srv) => return Err(ParseError {
+                                parent: "config".to_string(),
+                                name: name.clone(),
+                                kind: ErrorKind::DuplicateService,
+                                error: format!("duplicate service ({}:{}): {}", location.host, location.port, srv),
+                            }),
+                        }
+                    },
+                    None => return Err(ParseError {
+                        parent: "config".to_string(),
+                        name: name.clone(),
+                        kind: ErrorKind::UnknownHost,
+                        error: format!("unknown host: {}", location.host),
+                    }),
+                }
+            },
+            RunConf::None => continue,
+        }
+    }
+    Ok(())
+}
+
+// node names are unique, plus check_locations
+fn check_nodes<'t,I>(hosts: &BTreeMap<String,Host>, network: &NetworkPolicy, nodes: I) -> Result<(),ParseError>
+where I: IntoIterator<Item = &'t TopologyNode>
+{
+    let mut names = BTreeMap::new();
+    for n in nodes {
+        if let Some(name) = &n.name {
+            if names.insert(name,&n.config).is_some() {
+                return Err(ParseError {
+                    parent: n.parent.clone().unwrap_or_default(),
+                    name: name.clone(),
+                    kind: ErrorKind::DuplicateNode,
+                    error: "duplicate node".to_string(),
+                });
+            }
+        }
+    }
+    check_locations(hosts,network,names)
+}
+
+impl TryFrom<TomlTopology> for Topology {
+    type Error = ParseError;
+    fn try_from(t: TomlTopology) -> Result<Topology,ParseError> {
+        Topology::convert(t,&TopologyOptions::default(),&mut Vec::new())
+    }
+}
+
+impl Topology {
+    fn convert(t: TomlTopology, options: &TopologyOptions, warnings: &mut Vec<Warning>) -> Result<Topology,ParseError> {
+        let hosts = t.hosts;
+
+        //let mut passive = false;
+
+        let mut conf = BTreeMap::new();
+        let mut metas = BTreeMap::new();
+        run_conf(&None,t.config,&mut Configs {
+            map: &mut conf,
+            metas: &mut metas,
+            options,
+            warnings,
+        })?;
+
+        check_locations(&hosts,&t.network,&conf)?;
+        
+        let root = run_root(&None,t.root,&mut conf,&mut metas)?;
+        /*for r in root {
+            r.for_each(|node,_depth| {
+                println!("{:?}",node.name);
+                println!("   {:?}",node.parent);
+                println!("   {:?}",node.config);
+            });
+        }*/
+
+        Ok(Topology{
+            hosts,
+            root: TopologyNode {
+                name: None,
+                parent: None,
+                config: RunConf::None,
+                meta: serde_json::Map::new(),
+                node_type: TopologyNodeType::Node(root),
+            },
+            network: t.network,
+        })
+    }
+}
+
+impl std::str::FromStr for Topology {
+    type Err = LoadError;
+    fn from_str(s: &str) -> Result<Topology,LoadError> {
+        Topology::parse_with(None,s,&TopologyOptions::default()).map(|(t,_)| t)
+    }
+}
+
+// Canonical JSON, the input for topology digests:
+//   no whitespace, object keys sorted by their UTF-8 bytes, strings escaped as by serde_json,
+//   integral floats below 1e21 written as integers (1.0 -> 1, -0.0 -> 0),
+//   other floats in the shortest round-trip form
+fn write_canonical(v: &serde_json::Value, out: &mut String) {
+    match v {
+        serde_json::Value::Number(n) => match (n.is_f64(),n.as_f64()) {
+            (true,Some(f)) if f.fract() == 0.0 && f.abs() < 1e21 => *out += &format!("{:.0}",f + 0.0),
+            _ => *out += &n.to_string(),
+        },
+        serde_json::Value::Array(vs) => {
+            out.push('[');
+            for (i,v) in vs.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(v,out);
+            }
+            out.push(']');
+        },
+        serde_json::Value::Object(m) => {
+            let mut kvs = m.iter().collect::<Vec<_>>();
+            kvs.sort_by(|(a,_),(b,_)| a.as_bytes().cmp(b.as_bytes()));
+            out.push('{');
+            for (i,(k,v)) in kvs.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                *out += &serde_json::Value::String(k.clone()).to_string();
+                out.push(':');
+                write_canonical(v,out);
+            }
+            out.push('}');
+        },
+        v => *out += &v.to_string(),
+    }
+}
+
+fn publicity_str(p: &Publicity) -> &'static str {
+    match p {
+        Publicity::Local => "local",
+        Publicity::Internal => "internal",
+        Publicity::External => "external",
+    }
+}
+
+fn canonical_node(n: &TopologyNode) -> serde_json::Value {
+    use serde_json::json;
+
+    let location = |l: &Location| json!({
+        "host": l.host,
+        "port": l.port,
+        "publicity": l.publicity.as_ref().map(publicity_str),
+        "privileged": l.privileged,
+    });
+    let config = match &n.config {
+        RunConf::None => serde_json::Value::Null,
+        RunConf::Active{ params, location: l } => json!({ "mode": "active", "params": params, "location": location(l) }),
+        RunConf::Passive{ location: l } => json!({ "mode": "passive", "location": location(l) }),
+    };
+    let children = match &n.node_type {
+        TopologyNodeType::Terminal => serde_json::Value::Null,
+        TopologyNodeType::Node(v) => {
+            let mut v = v.iter().collect::<Vec<_>>();
+            v.sort_by(|a,b| a.name.cmp(&b.name));
+            v.into_iter().map(canonical_node).collect()
+        },
+    };
+    json!({ "name": n.name, "parent": n.parent, "config": config, "meta": n.meta, "children": children })
+}
+
+// placement of nodes on one physical host
+#[derive(Debug,PartialEq)]
+pub struct HostReport<'t> {
+    pub alias: &'t str,
+    pub host: &'t Host,
+    // sorted by port
+    pub nodes: Vec<PlacedNode<'t>>,
+}
+
+#[derive(Debug,PartialEq)]
+pub struct PlacedNode<'t> {
+    pub name: &'t str,
+    pub port: u16,
+    pub publicity: Option<&'t Publicity>,
+    pub active: bool,
+}
+
+// internal or local node reachable from an external one
+#[derive(Debug,PartialEq)]
+pub struct Exposure<'t> {
+    pub external: &'t str,
+    pub reached: &'t str,
+    pub publicity: &'t Publicity,
+    // node names from external to reached, both included
+    pub path: Vec<&'t str>,
+}
+
+impl Topology {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Topology,LoadError> {
+        Topology::from_file_with(path,&TopologyOptions::default()).map(|(t,_)| t)
+    }
+
+    // TOML with explicit options, also returns lossy conversion warnings
+    pub fn from_str_with(s: &str, options: &TopologyOptions) -> Result<(Topology,Vec<Warning>),LoadError> {
+        Topology::parse_with(None,s,options)
+    }
+
+    pub fn from_file_with(path: impl AsRef<Path>, options: &TopologyOptions) -> Result<(Topology,Vec<Warning>),LoadError> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path).map_err(|error| LoadError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        Topology::parse_with(Some(path),&s,options)
+    }
+
+    fn parse_with(path: Option<&Path>, s: &str, options: &TopologyOptions) -> Result<(Topology,Vec<Warning>),LoadError> {
+        let t: TomlTopology = match options.env_substitution {
+            false => toml::from_str(s).map_err(|e| LoadError::parse(path,s,e))?,
+            true => {
+                let err = |error: String| LoadError::Parse {
+                    path: path.map(Path::to_path_buf),
+                    line: None,
+                    column: None,
+                    error,
+                };
+                let mut t: toml::Table = toml::from_str(s).map_err(|e| LoadError::parse(path,s,e))?;
+                env_topology(&mut t).map_err(err)?;
+                TomlTopology::deserialize(toml::Value::Table(t)).map_err(|e| err(e.message().to_string()))?
+            },
+        };
+        let mut warnings = Vec::new();
+        let t = Topology::convert(t,options,&mut warnings).map_err(|error| LoadError::Invalid {
+            path: path.map(Path::to_path_buf),
+            error,
+        })?;
+        Ok((t,warnings))
+    }
+
+    // same layout as TOML: `hosts`, `root` and `config` objects
+    pub fn from_json_str(s: &str) -> Result<Topology,LoadError> {
+        let t: TomlTopology = serde_json::from_str(s).map_err(|e| LoadError::Parse {
+            path: None,
+            line: Some(e.line()),
+            column: Some(e.column()),
+            error: e.to_string(),
+        })?;
+        Topology::convert(t,&TopologyOptions::default(),&mut Vec::new()).map_err(|error| LoadError::Invalid{ path: None, error })
+    }
+
+    // same layout as TOML: `hosts`, `root` and `config` mappings
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml_str(s: &str) -> Result<Topology,LoadError> {
+        let t: TomlTopology = serde_yaml::from_str(s).map_err(|e| {
+            let location = e.location();
+            LoadError::Parse {
+                path: None,
+                line: location.as_ref().map(|l| l.line()),
+                column: location.as_ref().map(|l| l.column()),
+                error: e.to_string(),
+            }
+        })?;
+        Topology::convert(t,&TopologyOptions::default(),&mut Vec::new()).map_err(|error| LoadError::Invalid{ path: None, error })
+    }
+
+    // node by its dotted name, e.g. "r2.s.s-2"
+    pub fn get(&self, path: &str) -> Option<&TopologyNode> {
+        self.root.iter().find(|n| n.name.as_deref() == Some(path))
+    }
+
+    // nodes (Active and Passive) placed on host `alias`, in tree order
+    pub fn nodes_on_host(&self, alias: &str) -> Vec<&TopologyNode> {
+        self.root.iter()
+            .filter(|n| n.config.location().map(|l| l.host == alias).unwrap_or(false))
+            .collect()
+    }
+
+    // Canonical JSON of the whole topology (see write_canonical), equal topologies give equal
+    //   strings regardless of source format, platform or toml version, so it can be hashed for a digest.
+    // Children are sorted by name, forbidden ports and cidrs are sorted and deduplicated.
+    // Datetimes and non-finite floats in params are kept as converted while parsing (see TopologyOptions).
+    pub fn canonical_json(&self) -> String {
+        use serde_json::json;
+
+        let hosts = self.hosts.iter()
+            .map(|(alias,h)| (alias.clone(),json!({
+                "host": h.host,
+                "port": h.port,
+                "platform": h.platform.as_ref().map(|p| json!({ "os": p.os, "arch": p.arch })),
+            })))
+            .collect::<serde_json::Map<_,_>>();
+        let mut forbidden_ports = self.network.forbidden_ports.clone();
+        forbidden_ports.sort_unstable();
+        forbidden_ports.dedup();
+        let cidrs = self.network.cidrs.iter()
+            .map(|(p,cidrs)| {
+                let mut cidrs = cidrs.iter().map(Cidr::to_string).collect::<Vec<_>>();
+                cidrs.sort();
+                cidrs.dedup();
+                (publicity_str(p).to_string(),json!(cidrs))
+            })
+            .collect::<serde_json::Map<_,_>>();
+        let v = json!({
+            "hosts": hosts,
+            "network": { "forbidden_ports": forbidden_ports, "cidrs": cidrs },
+            "root": canonical_node(&self.root),
+        });
+        let mut res = String::new();
+        write_canonical(&v,&mut res);
+        res
+    }
+
+    // params of every Active node as the application config type, in tree order
+    pub fn typed_nodes<T>(&self) -> Result<Vec<(&str,T)>,ParseError>
+    where T: serde::de::DeserializeOwned
+    {
+        self.root.iter()
+            .filter_map(|n| match (&n.name,&n.config) {
+                (Some(name),RunConf::Active{ .. }) => Some((n,name)),
+                _ => None,
+            })
+            .map(|(n,name)| match n.config.params_as() {
+                Ok(t) => Ok((name.as_str(),t)),
+                Err(e) => Err(ParseError {
+                    parent: n.parent.clone().unwrap_or_default(),
+                    name: name.clone(),
+                    kind: ErrorKind::UnexpectedValue,
+                    error: e.to_string(),
+                }),
+            })
+            .collect()
+    }
+
+    // every host (including unused ones) with the nodes placed on it
+    pub fn hosts_report(&self) -> Vec<HostReport<'_>> {
+        let mut reports: BTreeMap<&str,HostReport> = self.hosts.iter()
+            .map(|(alias,host)| (alias.as_str(),HostReport {
+                alias,
+                host,
+                nodes: Vec::new(),
+            }))
+            .collect();
+        for n in self.root.iter() {
+            let (location,active) = match &n.config {
+                RunConf::Active{ location, .. } => (location,true),
+                RunConf::Passive{ location } => (location,false),
+                RunConf::None => continue,
+            };
+            if let (Some(name),Some(report)) = (&n.name,reports.get_mut(location.host.as_str())) {
+                report.nodes.push(PlacedNode {
+                    name,
+                    port: location.port,
+                    publicity: location.publicity.as_ref(),
+                    active,
+                });
+            }
+        }
+        reports.into_values()
+            .map(|mut report| {
+                report.nodes.sort_by_key(|n| n.port);
+                report
+            })
+            .collect()
+    }
+
+    // external nodes and the internal/local nodes they can transitively reach,
+    //   edges go from a node to its children
+    pub fn exposure(&self) -> Vec<Exposure<'_>> {
+        fn publicity(n: &TopologyNode) -> Option<&Publicity> {
+            match &n.config {
+                RunConf::Active{ location, .. } |
+                RunConf::Passive{ location } => location.publicity.as_ref(),
+                RunConf::None => None,
+            }
+        }
+        fn walk<'t>(node: &'t TopologyNode, path: &mut Vec<&'t str>, res: &mut Vec<Exposure<'t>>) {
+            if let TopologyNodeType::Node(v) = &node.node_type {
+                for n in v {
+                    let Some(name) = n.name.as_deref() else { continue };
+                    path.push(name);
+                    match publicity(n) {
+                        Some(p @ Publicity::Internal) |
+                        Some(p @ Publicity::Local) => res.push(Exposure {
+                            external: path[0],
+                            reached: name,
+                            publicity: p,
+                            path: path.clone(),
+                        }),
+                        Some(Publicity::External) | None => {},
+                    }
+                    walk(n,path,res);
+                    path.pop();
+                }
+            }
+        }
+        let mut res = Vec::new();
+        for n in self.root.iter() {
+            if let (Some(name),Some(Publicity::External)) = (n.name.as_deref(),publicity(n)) {
+                walk(n,&mut vec![name],&mut res);
+            }
+        }
+        res
+    }
+
+    // standalone topology holding the node `path` with its subtree and the hosts it is placed on
+    pub fn extract_subtree(&self, path: &str) -> Option<Topology> {
+        let node = self.get(path)?.clone();
+        let mut hosts = BTreeMap::new();
+        for n in node.iter() {
+            if let RunConf::Active{ location, .. } | RunConf::Passive{ location, .. } = &n.config {
+                if let Some(host) = self.hosts.get(&location.host) {
+                    hosts.insert(location.host.clone(),host.clone());
+                }
+            }
+        }
+        Some(Topology {
+            hosts,
+            root: TopologyNode {
+                name: None,
+                parent: None,
+                config: RunConf::None,
+                meta: serde_json::Map::new(),
+                node_type: TopologyNodeType::Node(vec![node]),
+            },
+            network: self.network.clone(),
+        })
+    }
+
+    // inverse of extract_subtree: top-level nodes of `sub` are attached to their parent node,
+    //   or to the root if the parent is not a node (same as for tables in [root]);
+    //   the network policy of `self` applies to the result
+    pub fn merge_subtree(&mut self, sub: Topology) -> Result<(),ParseError> {
+        for (alias,host) in &sub.hosts {
+            match self.hosts.get(alias) {
+                Some(h) if h != host => return Err(ParseError {
+                    parent: "hosts".to_string(),
+                    name: alias.clone(),
+                    kind: ErrorKind::ConflictingHost,
+                    error: format!("conflicting host: {:?} vs {:?}",h,host),
+                }),
+                _ => {},
+            }
+        }
+        let mut hosts = self.hosts.clone();
+        hosts.extend(sub.hosts.clone());
+
+        check_nodes(&hosts,&self.network,self.root.iter().chain(sub.root.iter()))?;
+
+        // attached into a copy, `self` is left untouched if any child fails
+        let mut root = self.root.clone();
+        if let TopologyNodeType::Node(children) = sub.root.node_type {
+            for child in children {
+                if let Some(child) = root.attach(child)? {
+                    if let TopologyNodeType::Node(v) = &mut root.node_type {
+                        v.push(child);
+                    }
+                }
+            }
+        }
+        self.root = root;
+        self.hosts = hosts;
+        Ok(())
+    }
+
+    // patches params of node `path` (e.g. "r2.s.s-2"), then re-validates the topology;
+    //   the node is left untouched on error
+    pub fn patch_params(&mut self, path: &str, patch: &ParamsPatch) -> Result<(),ParseError> {
+        let node = match self.root.iter_mut().find(|n| n.name.as_deref() == Some(path)) {
+            Some(node) => node,
+            None => return Err(ParseError {
+                parent: String::new(),
+                name: path.to_string(),
+                kind: ErrorKind::UnknownNode,
+                error: "unknown node".to_string(),
+            }),
+        };
+        let err = |node: &NodeMut, error: String| ParseError {
+            parent: node.parent.clone().unwrap_or_default(),
+            name: path.to_string(),
+            kind: ErrorKind::UnexpectedValue,
+            error,
+        };
+        let mut config = node.config.clone();
+        match patch {
+            ParamsPatch::Merge(patch) => config.params_merge(patch),
+            ParamsPatch::Json(ops) => config.params_json_patch(ops),
+        }.map_err(|e| err(&node,e.to_string()))?;
+        if !config.params().map(serde_json::Value::is_object).unwrap_or(true) {
+            return Err(err(&node,"params must be a table".to_string()));
+        }
+        let prev = std::mem::replace(node.config,config);
+
+        if let Err(e) = check_nodes(&self.hosts,&self.network,self.root.iter()) {
+            if let Some(node) = self.root.iter_mut().find(|n| n.name.as_deref() == Some(path)) {
+                *node.config = prev;
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+// Programmatic construction of a Topology, same checks as for parsing:
+//   TopologyBuilder::new()
+//       .host("r2", Host { host: "r2.local".to_string(), port: 25000, platform: None })
+//       .node("r2.s", RunConf::Active { .. })
+//       .terminal("r2.s.s-1", RunConf::Active { .. })
+//       .build()
+// a node is attached to the node named by its path prefix (that has to be added before),
+//   or to the root if there is no such node (like tables in [root])
+#[derive(Debug,Default)]
+pub struct TopologyBuilder {
+    hosts: BTreeMap<String,Host>,
+    nodes: Vec<TopologyNode>,
+    network: NetworkPolicy,
+}
+
+impl TopologyBuilder {
+    pub fn new() -> TopologyBuilder {
+        TopologyBuilder::default()
+    }
+
+    pub fn host(mut self, alias: &str, host: Host) -> TopologyBuilder {
+        self.hosts.insert(alias.to_string(),host);
+        self
+    }
+
+    pub fn network(mut self, network: NetworkPolicy) -> TopologyBuilder {
+        self.network = network;
+        self
+    }
+
+    // node which can have children
+    pub fn node(self, path: &str, config: RunConf) -> TopologyBuilder {
+        self.add(path,config,TopologyNodeType::Node(Vec::new()))
+    }
+
+    pub fn terminal(self, path: &str, config: RunConf) -> TopologyBuilder {
+        self.add(path,config,TopologyNodeType::Terminal)
+    }
+
+    fn add(mut self, path: &str, config: RunConf, node_type: TopologyNodeType) -> TopologyBuilder {
+        self.nodes.push(TopologyNode {
+            name: Some(path.to_string()),
+            parent: path.rsplit_once('.').map(|(parent,_)| parent.to_string()),
+            config,
+            meta: serde_json::Map::new(),
+            node_type,
+        });
+        self
+    }
+
+    pub fn build(self) -> Result<Topology,ParseError> {
+        let mut root = TopologyNode {
+            name: None,
+            parent: None,
+            config: RunConf::None,
+            meta: serde_json::Map::new(),
+            node_type: TopologyNodeType::Node(Vec::new()),
+        };
+        // first position of every node name
+        let mut added = BTreeMap::new();
+        for (i,node) in self.nodes.iter().enumerate() {
+            if let Some(name) = &node.name {
+                added.entry(name.clone()).or_insert(i);
+            }
+        }
+        for (i,node) in self.nodes.into_iter().enumerate() {
+            if let Some(parent) = &node.parent {
+                if added.get(parent).map(|p| *p > i).unwrap_or(false) {
+                    return Err(ParseError {
+                        parent: parent.clone(),
+                        name: node.name.clone().unwrap_or_default(),
+                        kind: ErrorKind::InvalidName,
+                        error: "parent node is added after its child".to_string(),
+                    });
+                }
+            }
+            if let Some(name) = &node.name {
+                if name.split('.').any(str::is_empty) {
+                    return Err(ParseError {
+                        parent: node.parent.clone().unwrap_or_default(),
+                        name: name.clone(),
+                        kind: ErrorKind::InvalidName,
+                        error: "invalid node path".to_string(),
+                    });
+                }
+            }
+            if let Some(node) = root.attach(node)? {
+                if let TopologyNodeType::Node(v) = &mut root.node_type {
+                    v.push(node);
+                }
+            }
+        }
+        check_nodes(&self.hosts,&self.network,root.iter())?;
+        Ok(Topology {
+            hosts: self.hosts,
+            root,
+            network: self.network,
+        })
+    }
+}
+
+// params patch, as accepted by Topology::patch_params
+#[derive(Debug,Clone,PartialEq)]
+pub enum ParamsPatch {
+    Merge(serde_json::Value), // RFC 7386 JSON Merge Patch
+    Json(Vec<PatchOp>),       // RFC 6902 JSON Patch
+}
+
+// RFC 6902 operation, e.g. { "op": "add", "path": "/data/-", "value": "data4" }
+#[derive(Debug,Clone,Deserialize,PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOp {
+    Add { path: String, value: serde_json::Value },
+    Remove { path: String },
+    Replace { path: String, value: serde_json::Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: serde_json::Value },
+}
+
+// splits JSON pointer into parent pointer and unescaped last token
+fn split_pointer(pointer: &str) -> Result<(&str,String),String> {
+    match pointer.rsplit_once('/') {
+        Some((parent,token)) if pointer.starts_with('/') => Ok((parent,token.replace("~1","/").replace("~0","~"))),
+        _ => Err(format!("invalid pointer: {}",pointer)),
+    }
+}
+
+fn array_index(token: &str, len: usize) -> Result<usize,String> {
+    match token.parse::<usize>() {
+        Ok(i) if i <= len && (token == "0" || !token.starts_with('0')) => Ok(i),
+        _ => Err(format!("invalid array index: {}",token)),
+    }
+}
+
+fn patch_add(doc: &mut serde_json::Value, path: &str, value: serde_json::Value) -> Result<(),String> {
+    if path.is_empty() {
+        *doc = value;
+        return Ok(());
+    }
+    let (parent,token) = split_pointer(path)?;
+    match doc.pointer_mut(parent) {
+        Some(serde_json::Value::Object(m)) => { m.insert(token,value); },
+        Some(serde_json::Value::Array(vs)) if token == "-" => vs.push(value),
+        Some(serde_json::Value::Array(vs)) => vs.insert(array_index(&token,vs.len())?,value),
+        Some(_) => return Err(format!("not a container: {}",parent)),
+        None => return Err(format!("path is missed: {}",parent)),
+    }
+    Ok(())
+}
+
+fn patch_remove(doc: &mut serde_json::Value, path: &str) -> Result<serde_json::Value,String> {
+    let (parent,token) = split_pointer(path)?;
+    let removed = match doc.pointer_mut(parent) {
+        Some(serde_json::Value::Object(m)) => m.remove(&token),
+        Some(serde_json::Value::Array(vs)) => match array_index(&token,vs.len()) {
+            Ok(i) if i < vs.len() => Some(vs.remove(i)),
+            _ => None,
+        },
+        _ => None,
+    };
+    removed.ok_or_else(|| format!("path is missed: {}",path))
+}
+
+// RFC 6902 JSON Patch, atomic: `doc` is left untouched if any operation fails
+fn json_patch(doc: &mut serde_json::Value, ops: &[PatchOp]) -> Result<(),ParamsError> {
+    let mut res = doc.clone();
+    for (i,op) in ops.iter().enumerate() {
+        let missed = |path: &str| format!("path is missed: {}",path);
+        match op {
+            PatchOp::Add{ path, value } => patch_add(&mut res,path,value.clone()),
+            PatchOp::Remove{ path } => patch_remove(&mut res,path).map(|_| ()),
+            PatchOp::Replace{ path, value } => match res.pointer_mut(path) {
+                Some(v) => {
+                    *v = value.clone();
+                    Ok(())
+                },
+                None => Err(missed(path)),
+            },
+            PatchOp::Move{ from, path } => match path.starts_with(&format!("{}/",from)) {
+                true => Err(format!("can't move {} into itself",from)),
+                false => patch_remove(&mut res,from).and_then(|v| patch_add(&mut res,path,v)),
+            },
+            PatchOp::Copy{ from, path } => match res.pointer(from).cloned() {
+                Some(v) => patch_add(&mut res,path,v),
+                None => Err(missed(from)),
+            },
+            PatchOp::Test{ path, value } => match res.pointer(path) {
+                Some(v) if v == value => Ok(()),
+                Some(v) => Err(format!("test {} failed: {}",path,v)),
+                None => Err(missed(path)),
+            },
+        }.map_err(|e| ParamsError::Patch(i,e))?;
+    }
+    *doc = res;
+    Ok(())
+}
+
+// RFC 7386 JSON Merge Patch
+fn merge_patch(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    match patch {
+        serde_json::Value::Object(patch) => {
+            if !target.is_object() {
+                *target = serde_json::Value::Object(serde_json::Map::new());
+            }
+            if let serde_json::Value::Object(target) = target {
+                for (k,v) in patch {
+                    match v {
+                        serde_json::Value::Null => { target.remove(k); },
+                        v => merge_patch(target.entry(k.clone()).or_insert(serde_json::Value::Null),v),
+                    }
+                }
+            }
+        },
+        patch => *target = patch.clone(),
+    }
+}
+
+impl RunConf {
+    pub fn params(&self) -> Option<&serde_json::Value> {
+        match self {
+            RunConf::Active{ params, .. } => Some(params),
+            RunConf::Passive{ .. } | RunConf::None => None,
+        }
+    }
+
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            RunConf::Active{ location, .. } |
+            RunConf::Passive{ location } => Some(location),
+            RunConf::None => None,
+        }
+    }
+
+    // params value at JSON pointer, e.g. "/data/0"
+    pub fn param_at(&self, pointer: &str) -> Option<&serde_json::Value> {
+        self.params()?.pointer(pointer)
+    }
+
+    // typed params value at JSON pointer, e.g. param_as::<String>("/mode")
+    pub fn param_as<'t,T>(&'t self, pointer: &str) -> Result<T,ParamsError>
+    where T: Deserialize<'t>
+    {
+        let params = self.params().ok_or(ParamsError::NoParams)?;
+        let v = params.pointer(pointer).ok_or_else(|| ParamsError::Missed(pointer.to_string()))?;
+        T::deserialize(v).map_err(|e| ParamsError::Invalid(pointer.to_string(),e))
+    }
+
+    // whole params as the application config type
+    pub fn params_as<T>(&self) -> Result<T,ParamsError>
+    where T: serde::de::DeserializeOwned
+    {
+        self.param_as("")
+    }
+
+    // applies `patch` to params as a JSON Merge Patch (RFC 7386): objects are merged, null removes a key
+    pub fn params_merge(&mut self, patch: &serde_json::Value) -> Result<(),ParamsError> {
+        match self {
+            RunConf::Active{ params, .. } => {
+                merge_patch(params,patch);
+                Ok(())
             },
-            v @ _ => return Err(ParseError{
-                parent: parent.clone().unwrap_or_else(||String::new()),
-                name,
-                error: format!("unexpected value: {:?}",v),
-            }),
+            RunConf::Passive{ .. } | RunConf::None => Err(ParamsError::NoParams),
+        }
+    }
+
+    // applies RFC 6902 JSON Patch `ops` to params, all or nothing
+    pub fn params_json_patch(&mut self, ops: &[PatchOp]) -> Result<(),ParamsError> {
+        match self {
+            RunConf::Active{ params, .. } => json_patch(params,ops),
+            RunConf::Passive{ .. } | RunConf::None => Err(ParamsError::NoParams),
         }
     }
-    Ok(())
 }
 
-impl TryFrom<TomlTopology> for Topology {
-    type Error = ParseError;
-    fn try_from(t: TomlTopology) -> Result<Topology,ParseError> {
-        let hosts = t.hosts;
+impl TopologyNode {
+    pub fn meta(&self) -> &serde_json::Map<String,serde_json::Value> {
+        &self.meta
+    }
 
-        //let mut passive = false;
+    // depth-first over the whole subtree (self first)
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            inner: self.iter_with_depth(),
+        }
+    }
 
-        let mut conf = BTreeMap::new();
-        run_conf(&None,t.config,&mut conf)?;
+    // same as iter, with depth relative to self (self is 0)
+    pub fn iter_with_depth(&self) -> IterWithDepth<'_> {
+        IterWithDepth {
+            stack: vec![(self,0)],
+        }
+    }
 
-        // check locations
-        let mut services = BTreeMap::new();
-        for (name,c) in &conf {
-            match c {
-                RunConf::Active{ location, .. } |
-                RunConf::Passive{ location, .. } => {
-                    match hosts.contains_key(&location.host) {
-                        true => {
-                            let s = format!("{}:{}",location.host,location.port);
-                            match services.get(&s) {
-                                None => { services.insert(s,name); },
-                                Some(srv) => return Err(ParseError {
-                                    parent: "config".to_string(),
-                                    name: name.clone(),
-                                    error: format!("duplicate service ({}:{}): {}", location.host, location.port, srv),
-                                }),
-                            }
-                        },
-                        false => return Err(ParseError {
-                            parent: "config".to_string(),
-                            name: name.clone(),
-                            error: format!("unknown host: {}", location.host),
-                        }),
-                    }
-                },
-                RunConf::None => continue,
+    // depth-first over the whole subtree (self first), with depth relative to self (self is 0)
+    pub fn for_each<'t,F>(&'t self, mut f: F)
+    where F: FnMut(&'t TopologyNode, usize)
+    {
+        for (n,depth) in self.iter_with_depth() {
+            f(n,depth);
+        }
+    }
+
+    // same order as for_each, children are visited after `f` returns for their parent
+    pub fn for_each_mut<F>(&mut self, mut f: F)
+    where F: FnMut(&mut TopologyNode, usize)
+    {
+        fn walk<F>(node: &mut TopologyNode, depth: usize, f: &mut F)
+        where F: FnMut(&mut TopologyNode, usize)
+        {
+            f(node,depth);
+            if let TopologyNodeType::Node(v) = &mut node.node_type {
+                for n in v {
+                    walk(n,depth + 1,f);
+                }
             }
         }
-        
-        let root = run_root(&None,t.root,&mut conf)?;
-        /*for r in root {
-            r.for_each(|node| {
-                println!("{:?}",node.name);
-                println!("   {:?}",node.parent);
-                println!("   {:?}",node.config);
-            });
-        }*/
+        walk(self,0,&mut f);
+    }
 
-        Ok(Topology{
-            hosts,
-            root: TopologyNode {
-                name: None,
-                parent: None,
-                config: RunConf::None,
-                node_type: TopologyNodeType::Node(root),
-            },
-        })
+    // same order as iter; names and tree shape stay read-only, only configs can be changed
+    pub fn iter_mut(&mut self) -> IterMut<'_> {
+        IterMut {
+            stack: vec![self],
+        }
     }
-}
 
-impl TopologyNode {
-    pub fn for_each<F>(&self, mut f: F)
-    where F: FnMut(&TopologyNode)
+    // direct children only
+    pub fn for_each_child<'t,F>(&'t self, mut f: F)
+    where F: FnMut(&'t TopologyNode)
     {
-        f(self);
         match &self.node_type {
             TopologyNodeType::Node(v) => for n in v {
                 f(n);
@@ -286,14 +1567,89 @@ impl TopologyNode {
             TopologyNodeType::Terminal => {},
         }
     }
+
+    // puts `child` under the node named as its parent, gives it back if there is no such node
+    fn attach(&mut self, child: TopologyNode) -> Result<Option<TopologyNode>,ParseError> {
+        if child.parent.is_some() && self.name == child.parent {
+            return match &mut self.node_type {
+                TopologyNodeType::Node(v) => {
+                    v.push(child);
+                    Ok(None)
+                },
+                TopologyNodeType::Terminal => Err(ParseError {
+                    parent: child.parent.clone().unwrap_or_default(),
+                    name: child.name.clone().unwrap_or_default(),
+                    kind: ErrorKind::TerminalParent,
+                    error: "parent is a terminal node".to_string(),
+                }),
+            };
+        }
+        let mut child = child;
+        if let TopologyNodeType::Node(v) = &mut self.node_type {
+            for n in v {
+                child = match n.attach(child)? {
+                    None => return Ok(None),
+                    Some(child) => child,
+                };
+            }
+        }
+        Ok(Some(child))
+    }
+}
+
+pub struct IterWithDepth<'t> {
+    stack: Vec<(&'t TopologyNode,usize)>,
+}
+impl<'t> Iterator for IterWithDepth<'t> {
+    type Item = (&'t TopologyNode,usize);
+    fn next(&mut self) -> Option<(&'t TopologyNode,usize)> {
+        let (node,depth) = self.stack.pop()?;
+        if let TopologyNodeType::Node(v) = &node.node_type {
+            self.stack.extend(v.iter().rev().map(|n| (n,depth + 1)));
+        }
+        Some((node,depth))
+    }
+}
+
+pub struct Iter<'t> {
+    inner: IterWithDepth<'t>,
+}
+
+impl<'t> Iterator for Iter<'t> {
+    type Item = &'t TopologyNode;
+    fn next(&mut self) -> Option<&'t TopologyNode> {
+        self.inner.next().map(|(n,_)| n)
+    }
+}
+
+pub struct NodeMut<'t> {
+    pub name: &'t Option<String>,
+    pub parent: &'t Option<String>,
+    pub config: &'t mut RunConf,
+    pub meta: &'t mut serde_json::Map<String,serde_json::Value>,
+}
+
+pub struct IterMut<'t> {
+    stack: Vec<&'t mut TopologyNode>,
+}
+impl<'t> Iterator for IterMut<'t> {
+    type Item = NodeMut<'t>;
+    fn next(&mut self) -> Option<NodeMut<'t>> {
+        let TopologyNode{ name, parent, config, meta, node_type } = self.stack.pop()?;
+        if let TopologyNodeType::Node(v) = node_type {
+            self.stack.extend(v.iter_mut().rev());
+        }
+        Some(NodeMut{ name, parent, config, meta })
+    }
 }
 
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
-    fn example() -> &'static str {
+    // shared by the tests of the other modules
+    pub(crate) fn example() -> &'static str {
         "# Hosts
 
 [hosts]
@@ -406,8 +1762,8 @@ location = { host = \"r2\", port = 25103, publicity = \"local\" }
         let t: TomlTopology = toml::from_str(example()).unwrap();
 
         let r = TomlTopology {
-            hosts: vec![("r1".to_string(), Host { host: "r1.local".to_string(), port: 25000 }),
-                        ("r2".to_string(), Host { host: "r2.local".to_string(), port: 25000 })]
+            hosts: vec![("r1".to_string(), Host { host: "r1.local".to_string(), port: 25000, platform: None }),
+                        ("r2".to_string(), Host { host: "r2.local".to_string(), port: 25000, platform: None })]
                 .into_iter()
                 .collect(),
             root: vec_into_table(vec![
@@ -456,6 +1812,7 @@ location = { host = \"r2\", port = 25103, publicity = \"local\" }
                     ]))),
                 ]))),
             ]),
+            network: NetworkPolicy::default(),
         };
         
         assert_eq!(t,r);
@@ -468,32 +1825,36 @@ location = { host = \"r2\", port = 25103, publicity = \"local\" }
         let t: Topology = toml::from_str(example()).unwrap();
 
         let r = Topology {
-            hosts: vec![("r1".to_string(), Host { host: "r1.local".to_string(), port: 25000 }),
-                        ("r2".to_string(), Host { host: "r2.local".to_string(), port: 25000 })]
+            hosts: vec![("r1".to_string(), Host { host: "r1.local".to_string(), port: 25000, platform: None }),
+                        ("r2".to_string(), Host { host: "r2.local".to_string(), port: 25000, platform: None })]
                 .into_iter()
                 .collect(),
             root: TopologyNode {
                 name: None,
                 parent: None,
                 config: RunConf::None,
+                meta: serde_json::Map::new(),
                 node_type: TopologyNodeType::Node(vec![
                     TopologyNode {
                         name: Some("r1".to_string()),
                         parent: None,
                         config: RunConf::Active { params: json!({ "cache": true, "mode": "p" }),
-                                                  location: Location { host: "r1".to_string(), port: 25100, publicity: Some(Publicity::Internal) } },
+                                                  location: Location { host: "r1".to_string(), port: 25100, publicity: Some(Publicity::Internal), privileged: false } },
+                        meta: serde_json::Map::new(),
                         node_type: TopologyNodeType::Node(vec![
                             TopologyNode {
                                 name: Some("r1.d-a".to_string()),
                                 parent: Some("r1".to_string()),
                                 config: RunConf::Active { params: json!({ "data": [ "data1" ], "mode": "d" }),
-                                                          location: Location { host: "r1".to_string(), port: 25101, publicity: Some(Publicity::Local) } },
+                                                          location: Location { host: "r1".to_string(), port: 25101, publicity: Some(Publicity::Local), privileged: false } },
+                                meta: serde_json::Map::new(),
                                 node_type: TopologyNodeType::Terminal },
                             TopologyNode {
                                 name: Some("r1.s-2".to_string()),
                                 parent: Some("r1".to_string()),
                                 config: RunConf::Active { params: json!({"data": [ "data2", "data3" ], "mode": "s" }),
-                                                          location: Location { host: "r1".to_string(), port: 25102, publicity: None } },
+                                                          location: Location { host: "r1".to_string(), port: 25102, publicity: None, privileged: false } },
+                                meta: serde_json::Map::new(),
                                 node_type: TopologyNodeType::Terminal }
                         ])
                     },
@@ -501,38 +1862,657 @@ location = { host = \"r2\", port = 25103, publicity = \"local\" }
                         name: Some("r2.d".to_string()),
                         parent: Some("r2".to_string()),
                         config: RunConf::Active { params: json!({ "mode": "p" }),
-                                                  location: Location { host: "r2".to_string(), port: 25200, publicity: Some(Publicity::Internal) } },
+                                                  location: Location { host: "r2".to_string(), port: 25200, publicity: Some(Publicity::Internal), privileged: false } },
+                        meta: serde_json::Map::new(),
                         node_type: TopologyNodeType::Node(vec![]) },
                     TopologyNode {
                         name: Some("r2.s".to_string()),
                         parent: Some("r2".to_string()),
                         config: RunConf::Active { params: json!({ "mode": "p" }),
-                                                  location: Location { host: "r2".to_string(), port: 25201, publicity: Some(Publicity::Internal) } },
+                                                  location: Location { host: "r2".to_string(), port: 25201, publicity: Some(Publicity::Internal), privileged: false } },
+                        meta: serde_json::Map::new(),
                         node_type: TopologyNodeType::Node(vec![
                             TopologyNode {
                                 name: Some("r2.s.s-1".to_string()),
                                 parent: Some("r2.s".to_string()),
                                 config: RunConf::Active { params: json!({ "data": [ "data1" ], "mode": "s" }),
-                                                          location: Location { host: "r2".to_string(), port: 25101, publicity: Some(Publicity::Local) } },
+                                                          location: Location { host: "r2".to_string(), port: 25101, publicity: Some(Publicity::Local), privileged: false } },
+                                meta: serde_json::Map::new(),
                                 node_type: TopologyNodeType::Terminal },
                             TopologyNode {
                                 name: Some("r2.s.s-2".to_string()),
                                 parent: Some("r2.s".to_string()),
                                 config: RunConf::Active { params: json!({ "data": [ "data2" ], "mode": "s" }),
-                                                          location: Location { host: "r2".to_string(), port: 25102, publicity: Some(Publicity::Local) } },
+                                                          location: Location { host: "r2".to_string(), port: 25102, publicity: Some(Publicity::Local), privileged: false } },
+                                meta: serde_json::Map::new(),
                                 node_type: TopologyNodeType::Terminal },
                             TopologyNode {
                                 name: Some("r2.s.s-3".to_string()),
                                 parent: Some("r2.s".to_string()),
                                 config: RunConf::Active { params: json!({ "data": [ "data3" ], "mode": "s" }),
-                                                          location: Location { host: "r2".to_string(), port: 25103, publicity: Some(Publicity::Local) } },
+                                                          location: Location { host: "r2".to_string(), port: 25103, publicity: Some(Publicity::Local), privileged: false } },
+                                meta: serde_json::Map::new(),
                                 node_type: TopologyNodeType::Terminal }
                         ])
                     }                    
                 ])                
-            }
+            },
+            network: NetworkPolicy::default(),
         };
         
         assert_eq!(t,r);
     }
+
+    #[test]
+    fn host_platform() {
+        let t: Topology = toml::from_str(&example().replace(
+            "r2 = { host = \"r2.local\", port = 25000 }",
+            "r2 = { host = \"r2.local\", port = 25000, platform = { os = \"linux\", arch = \"aarch64\" } }",
+        )).unwrap();
+
+        assert_eq!(t.hosts["r1"].platform,None);
+        assert_eq!(t.hosts["r2"].platform,Some(Platform { os: "linux".to_string(), arch: "aarch64".to_string() }));
+    }
+
+    #[test]
+    fn subtree_extract_merge() {
+        let t: Topology = toml::from_str(example()).unwrap();
+        let names = |n: &TopologyNode| match &n.node_type {
+            TopologyNodeType::Node(v) => v.iter().map(|n| n.name.clone().unwrap()).collect::<Vec<_>>(),
+            TopologyNodeType::Terminal => Vec::new(),
+        };
+
+        assert_eq!(t.extract_subtree("r2"),None);
+
+        let sub = t.extract_subtree("r2.s").unwrap();
+        assert_eq!(sub.hosts.keys().collect::<Vec<_>>(),vec!["r2"]);
+        assert_eq!(names(&sub.root),vec!["r2.s"]);
+
+        let mut r1 = t.extract_subtree("r1").unwrap();
+        r1.merge_subtree(sub).unwrap();
+        assert_eq!(r1.hosts,t.hosts);
+        assert_eq!(names(&r1.root),vec!["r1","r2.s"]);
+
+        let dup = t.extract_subtree("r2.s").unwrap();
+        assert!(r1.merge_subtree(dup).is_err());
+
+        let mut partial: Topology = toml::from_str(&example().replace(
+            "s = [\"s-1\", \"s-2\", \"s-3\"]",
+            "s = [\"s-2\", \"s-3\"]",
+        )).unwrap();
+        partial.merge_subtree(t.extract_subtree("r2.s.s-1").unwrap()).unwrap();
+        let s = partial.extract_subtree("r2.s").unwrap();
+        let TopologyNodeType::Node(v) = &s.root.node_type else { unreachable!() };
+        assert_eq!(names(&v[0]),vec!["r2.s.s-2","r2.s.s-3","r2.s.s-1"]);
+
+        let location = |host: &str, port| RunConf::Active {
+            params: serde_json::json!({}),
+            location: Location { host: host.to_string(), port, publicity: None, privileged: false },
+        };
+        let mut sub = TopologyBuilder::new()
+            .host("r3",Host { host: "r3.local".to_string(), port: 25000, platform: None })
+            .node("r9",location("r3",25100))
+            .build().unwrap();
+        if let TopologyNodeType::Node(v) = &mut sub.root.node_type {
+            v.push(TopologyNode {
+                name: Some("r1.d-a.x".to_string()),
+                parent: Some("r1.d-a".to_string()),
+                config: location("r1",25109),
+                meta: serde_json::Map::new(),
+                node_type: TopologyNodeType::Terminal,
+            });
+        }
+        let mut r1 = t.extract_subtree("r1").unwrap();
+        let before = t.extract_subtree("r1").unwrap();
+        assert_eq!(r1.merge_subtree(sub).unwrap_err().kind(),ErrorKind::TerminalParent);
+        assert_eq!(r1,before);
+    }
+
+    #[test]
+    fn topology_load() {
+        let t: Topology = example().parse().unwrap();
+        assert_eq!(t,toml::from_str(example()).unwrap());
+
+        let path = std::env::temp_dir().join("universum_topology_load.toml");
+        std::fs::write(&path,example()).unwrap();
+        assert_eq!(Topology::from_file(&path).unwrap(),t);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(Topology::from_file(&path),Err(LoadError::Io{ .. })));
+
+        match example().replace("port = 25102 }","port = 25102").parse::<Topology>() {
+            Err(LoadError::Parse{ path: None, line: Some(32), column: Some(39), .. }) => {},
+            r => panic!("unexpected: {:?}",r),
+        }
+
+        std::fs::write(&path,example().replace("port = 25102 }","port = 25101 }")).unwrap();
+        let r = Topology::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        match r {
+            Err(LoadError::Invalid{ path: Some(p), error }) => {
+                assert_eq!(p,path);
+                assert_eq!(error.kind(),ErrorKind::DuplicateService);
+            },
+            r => panic!("unexpected: {:?}",r),
+        }
+    }
+
+    #[test]
+    fn topology_json() {
+        let table: toml::Table = toml::from_str(example()).unwrap();
+        let json = serde_json::to_string_pretty(&table).unwrap();
+        let t = Topology::from_json_str(&json).unwrap();
+        assert_eq!(t,example().parse().unwrap());
+
+        match Topology::from_json_str(&json.replace("25102","25101")) {
+            Err(LoadError::Invalid{ error, .. }) => assert_eq!(error.kind(),ErrorKind::DuplicateService),
+            r => panic!("unexpected: {:?}",r),
+        }
+        match Topology::from_json_str("{\n  \"hosts\": {\n    ,") {
+            Err(LoadError::Parse{ line: Some(3), .. }) => {},
+            r => panic!("unexpected: {:?}",r),
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn topology_yaml() {
+        let yaml = "
+hosts:
+  r1: { host: r1.local, port: 25000 }
+  r2: { host: r2.local, port: 25000 }
+root:
+  r1: [d-a, s-2]
+  r2:
+    d: []
+    s: [s-1, s-2, s-3]
+config:
+  r1:
+    params: { mode: p, cache: true }
+    location: { host: r1, port: 25100, publicity: internal }
+    d-a:
+      params: { mode: d, data: [data1] }
+      location: { host: r1, port: 25101, publicity: local }
+    s-2:
+      params: { mode: s, data: [data2, data3] }
+      location: { host: r1, port: 25102 }
+  r2:
+    params: { mode: p, cache: true }
+    location: { host: r2, port: 25100, publicity: internal }
+    d:
+      params: { mode: p }
+      location: { host: r2, port: 25200, publicity: internal }
+    s:
+      params: { mode: p }
+      location: { host: r2, port: 25201, publicity: internal }
+      s-1:
+        params: { mode: s, data: [data1] }
+        location: { host: r2, port: 25101, publicity: local }
+      s-2:
+        params: { mode: s, data: [data2] }
+        location: { host: r2, port: 25102, publicity: local }
+      s-3:
+        params: { mode: s, data: [data3] }
+        location: { host: r2, port: 25103, publicity: local }
+";
+        let t = Topology::from_yaml_str(yaml).unwrap();
+        assert_eq!(t,example().parse().unwrap());
+
+        assert!(Topology::from_yaml_str(&yaml.replace("port: 25102 }","port: 25101 }")).is_err());
+    }
+
+    #[test]
+    fn topology_builder() {
+        use serde_json::json;
+
+        let t: Topology = example().parse().unwrap();
+
+        let active = |params: serde_json::Value, host: &str, port: u16, publicity: Option<Publicity>| RunConf::Active {
+            params,
+            location: Location { host: host.to_string(), port, publicity, privileged: false },
+        };
+        let host = |host: &str| Host { host: host.to_string(), port: 25000, platform: None };
+        let builder = || TopologyBuilder::new()
+            .host("r1",host("r1.local"))
+            .host("r2",host("r2.local"))
+            .node("r1",active(json!({ "cache": true, "mode": "p" }),"r1",25100,Some(Publicity::Internal)))
+            .terminal("r1.d-a",active(json!({ "data": [ "data1" ], "mode": "d" }),"r1",25101,Some(Publicity::Local)))
+            .terminal("r1.s-2",active(json!({ "data": [ "data2", "data3" ], "mode": "s" }),"r1",25102,None))
+            .node("r2.d",active(json!({ "mode": "p" }),"r2",25200,Some(Publicity::Internal)))
+            .node("r2.s",active(json!({ "mode": "p" }),"r2",25201,Some(Publicity::Internal)))
+            .terminal("r2.s.s-1",active(json!({ "data": [ "data1" ], "mode": "s" }),"r2",25101,Some(Publicity::Local)))
+            .terminal("r2.s.s-2",active(json!({ "data": [ "data2" ], "mode": "s" }),"r2",25102,Some(Publicity::Local)))
+            .terminal("r2.s.s-3",active(json!({ "data": [ "data3" ], "mode": "s" }),"r2",25103,Some(Publicity::Local)));
+
+        assert_eq!(builder().build().unwrap(),t);
+
+        // unknown host
+        assert!(builder().terminal("r1.x",active(json!({}),"r3",25103,None)).build().is_err());
+        // duplicate service
+        assert!(builder().terminal("r1.x",active(json!({}),"r2",25103,None)).build().is_err());
+        // duplicate node
+        assert!(builder().terminal("r1.d-a",active(json!({}),"r1",25104,None)).build().is_err());
+        // child of a terminal node
+        assert!(builder().terminal("r1.d-a.x",active(json!({}),"r1",25104,None)).build().is_err());
+        // empty path segment
+        assert!(builder().terminal("r1..x",active(json!({}),"r1",25104,None)).build().is_err());
+        // parent added after its child
+        let e = TopologyBuilder::new()
+            .host("r1",Host { host: "r1.local".to_string(), port: 25000, platform: None })
+            .terminal("r1.a",active(json!({}),"r1",25101,None))
+            .node("r1",active(json!({}),"r1",25100,None))
+            .build().unwrap_err();
+        assert_eq!((e.kind(),e.parent(),e.name()),(ErrorKind::InvalidName,"r1","r1.a"));
+    }
+
+    #[test]
+    fn parse_error_kind() {
+        let bad = |from: &str, to: &str| {
+            let t: TomlTopology = toml::from_str(&example().replace(from,to)).unwrap();
+            Topology::try_from(t).unwrap_err()
+        };
+
+        let e = bad("port = 25102 }","port = 25101 }");
+        assert_eq!(e.kind(),ErrorKind::DuplicateService);
+        assert_eq!(e.parent(),"config");
+        assert_eq!(e.name(),"r1.s-2");
+
+        let e = bad("host = \"r2\", port = 25103","host = \"r3\", port = 25103");
+        assert_eq!(e.kind(),ErrorKind::UnknownHost);
+        assert_eq!(e.name(),"r2.s.s-3");
+
+        let e = bad("\"s-1\", \"s-2\", \"s-3\"","\"s-1\", \"s-2\", \"s-4\"");
+        assert_eq!(e.kind(),ErrorKind::MissedConfig);
+        assert_eq!((e.parent(),e.name()),("r2.s","s-4"));
+
+        let e: Box<dyn std::error::Error> = Box::new(bad("[config.r1.d-a]\nparams","[config.r1.d-a]\nxparams"));
+        assert_eq!(e.to_string(),"r1.d-a: conf 'params' is missed");
+    }
+
+    #[test]
+    fn hosts_report() {
+        let t: Topology = example().parse().unwrap();
+        let r = t.hosts_report();
+        assert_eq!(r.len(),2);
+        assert_eq!((r[0].alias,r[0].host.host.as_str()),("r1","r1.local"));
+        assert_eq!(r[0].nodes,vec![
+            PlacedNode { name: "r1", port: 25100, publicity: Some(&Publicity::Internal), active: true },
+            PlacedNode { name: "r1.d-a", port: 25101, publicity: Some(&Publicity::Local), active: true },
+            PlacedNode { name: "r1.s-2", port: 25102, publicity: None, active: true },
+        ]);
+        assert_eq!(r[1].alias,"r2");
+        assert_eq!(r[1].nodes.iter().map(|n| (n.name,n.port)).collect::<Vec<_>>(),vec![
+            ("r2.s.s-1",25101),("r2.s.s-2",25102),("r2.s.s-3",25103),("r2.d",25200),("r2.s",25201),
+        ]);
+    }
+
+    #[test]
+    fn nodes_on_host() {
+        let t: Topology = example().parse().unwrap();
+        let names = |alias| t.nodes_on_host(alias).into_iter().filter_map(|n| n.name.as_deref()).collect::<Vec<_>>();
+        assert_eq!(names("r1"),vec!["r1","r1.d-a","r1.s-2"]);
+        assert_eq!(names("r2"),vec!["r2.d","r2.s","r2.s.s-1","r2.s.s-2","r2.s.s-3"]);
+        assert_eq!(names("r3"),Vec::<&str>::new());
+    }
+
+    #[test]
+    fn exposure() {
+        let t: Topology = example().parse().unwrap();
+        assert_eq!(t.exposure(),vec![]);
+
+        let t: Topology = example()
+            .replace("port = 25201, publicity = \"internal\"","port = 25201, publicity = \"external\"")
+            .replace("port = 25102, publicity = \"local\"","port = 25102, publicity = \"external\"")
+            .parse().unwrap();
+        let e = t.exposure();
+        assert_eq!(e.iter().map(|e| (e.external,e.reached,e.publicity)).collect::<Vec<_>>(),vec![
+            ("r2.s","r2.s.s-1",&Publicity::Local),
+            ("r2.s","r2.s.s-3",&Publicity::Local),
+        ]);
+        assert_eq!(e[0].path,vec!["r2.s","r2.s.s-1"]);
+    }
+
+    #[test]
+    fn node_for_each() {
+        let t: Topology = example().parse().unwrap();
+
+        let mut visited = Vec::new();
+        t.root.for_each(|n,depth| visited.push((n.name.as_deref().unwrap_or(""),depth)));
+        assert_eq!(visited,vec![
+            ("",0),
+            ("r1",1),("r1.d-a",2),("r1.s-2",2),
+            ("r2.d",1),
+            ("r2.s",1),("r2.s.s-1",2),("r2.s.s-2",2),("r2.s.s-3",2),
+        ]);
+
+        let mut children = Vec::new();
+        t.root.for_each_child(|n| children.extend(n.name.as_deref()));
+        assert_eq!(children,vec!["r1","r2.d","r2.s"]);
+    }
+
+    #[test]
+    fn network_policy() {
+        let cidr: Cidr = "10.1.0.0/16".parse().unwrap();
+        assert!(cidr.contains(&"10.1.2.3".parse().unwrap()));
+        assert!(!cidr.contains(&"10.2.0.1".parse().unwrap()));
+        assert!(!cidr.contains(&"::1".parse().unwrap()));
+        assert!("0.0.0.0/0".parse::<Cidr>().unwrap().contains(&"1.2.3.4".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        let wide = Cidr{ addr: "10.0.0.0".parse().unwrap(), prefix: 40 };
+        assert!(!wide.contains(&"10.0.0.0".parse().unwrap()));
+        assert!(!wide.contains(&"192.168.0.1".parse().unwrap()));
+        let wide = Cidr{ addr: "::".parse().unwrap(), prefix: 200 };
+        assert!(!wide.contains(&"::".parse().unwrap()));
+        assert!(!wide.contains(&"2001:db8::1".parse().unwrap()));
+
+        let with_network = |network: &str| {
+            let s = example()
+                .replace("r1.local","10.1.0.1")
+                .replace("r2.local","10.2.0.1")
+                + network;
+            let t: TomlTopology = toml::from_str(&s).unwrap();
+            Topology::try_from(t)
+        };
+
+        let t = with_network("
+[network]
+forbidden_ports = [22]
+cidrs = { internal = [\"10.0.0.0/8\"], local = [\"10.1.0.0/16\", \"10.2.0.0/16\"] }
+").unwrap();
+        assert_eq!(t.network.forbidden_ports,vec![22]);
+        assert_eq!(t.network.cidrs[&Publicity::Local].len(),2);
+
+        let e = with_network("\n[network]\nforbidden_ports = [25101]\n").unwrap_err();
+        assert_eq!((e.kind(),e.name()),(ErrorKind::NetworkPolicy,"r1.d-a"));
+
+        let e = with_network("\n[network]\ncidrs = { local = [\"10.1.0.0/16\"] }\n").unwrap_err();
+        assert_eq!((e.kind(),e.name()),(ErrorKind::NetworkPolicy,"r2.s.s-1"));
+
+        let e = Topology::try_from(toml::from_str::<TomlTopology>(&(example().to_string()
+            + "\n[network]\ncidrs = { local = [\"10.1.0.0/16\"] }\n")).unwrap()).unwrap_err();
+        assert_eq!(e.kind(),ErrorKind::NetworkPolicy);
+    }
+
+    #[test]
+    fn node_iter() {
+        let t: Topology = example().parse().unwrap();
+
+        let terminals = t.root.iter()
+            .filter(|n| n.node_type == TopologyNodeType::Terminal)
+            .filter_map(|n| n.name.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(terminals,vec!["r1.d-a","r1.s-2","r2.s.s-1","r2.s.s-2","r2.s.s-3"]);
+
+        let mut visited = Vec::new();
+        t.root.for_each(|n,depth| visited.push((n,depth)));
+        assert_eq!(t.root.iter_with_depth().collect::<Vec<_>>(),visited);
+        assert_eq!(t.root.iter().count(),9);
+    }
+
+    #[test]
+    fn node_iter_mut() {
+        let mut t: Topology = example().parse().unwrap();
+
+        for n in t.root.iter_mut() {
+            if let RunConf::Active{ params, location } = n.config {
+                if n.parent.as_deref() == Some("r2.s") {
+                    params["env"] = "prod".into();
+                    location.port += 1000;
+                }
+            }
+        }
+        let mut depths = Vec::new();
+        t.root.for_each_mut(|n,depth| {
+            depths.push(depth);
+            if let RunConf::Active{ location, .. } = &mut n.config {
+                location.publicity.get_or_insert(Publicity::Local);
+            }
+        });
+        assert_eq!(depths,vec![0,1,2,2,1,1,2,2,2]);
+
+        let s_1 = t.get("r2.s.s-1").unwrap();
+        assert_eq!(s_1.config,RunConf::Active {
+            params: serde_json::json!({ "data": [ "data1" ], "env": "prod", "mode": "s" }),
+            location: Location { host: "r2".to_string(), port: 26101, publicity: Some(Publicity::Local), privileged: false },
+        });
+        let s_2 = t.get("r1.s-2").unwrap();
+        let RunConf::Active{ location, .. } = &s_2.config else { unreachable!() };
+        assert_eq!((location.port,&location.publicity),(25102,&Some(Publicity::Local)));
+    }
+
+    #[test]
+    fn topology_get() {
+        let t: Topology = example().parse().unwrap();
+        let n = t.get("r2.s.s-2").unwrap();
+        assert_eq!((n.parent.as_deref(),&n.node_type),(Some("r2.s"),&TopologyNodeType::Terminal));
+        assert_eq!(t.get("r1").unwrap().parent,None);
+        assert_eq!(t.get("r2"),None);
+        assert_eq!(t.get("r2.s.s-4"),None);
+        assert_eq!(t.get(""),None);
+    }
+
+    #[test]
+    fn params_access() {
+        use serde_json::json;
+
+        let t: Topology = example().parse().unwrap();
+        let c = &t.get("r1.s-2").unwrap().config;
+        assert_eq!(c.param_at("/data/1"),Some(&json!("data3")));
+        assert_eq!(c.param_at("/data/2"),None);
+        assert_eq!(c.param_as::<&str>("/mode").unwrap(),"s");
+        assert_eq!(c.param_as::<Vec<String>>("/data").unwrap(),vec!["data2","data3"]);
+        assert!(matches!(c.param_as::<bool>("/mode"),Err(ParamsError::Invalid(..))));
+        assert!(matches!(c.param_as::<bool>("/cache"),Err(ParamsError::Missed(..))));
+        assert!(matches!(t.root.config.param_as::<bool>("/cache"),Err(ParamsError::NoParams)));
+
+        let mut c = c.clone();
+        c.params_merge(&json!({ "mode": null, "data": ["data4"], "limits": { "conn": 10 } })).unwrap();
+        assert_eq!(c.params(),Some(&json!({ "data": ["data4"], "limits": { "conn": 10 } })));
+        c.params_merge(&json!({ "limits": { "rps": 5 } })).unwrap();
+        assert_eq!(c.param_at("/limits"),Some(&json!({ "conn": 10, "rps": 5 })));
+        assert!(RunConf::None.params_merge(&json!({})).is_err());
+    }
+
+    #[test]
+    fn patch_params() {
+        use serde_json::json;
+
+        let mut t: Topology = example().parse().unwrap();
+        t.patch_params("r1.s-2",&ParamsPatch::Merge(json!({ "mode": null, "cache": 64 }))).unwrap();
+        assert_eq!(t.get("r1.s-2").unwrap().config.params(),Some(&json!({ "data": ["data2","data3"], "cache": 64 })));
+
+        let ops: Vec<PatchOp> = serde_json::from_value(json!([
+            { "op": "test", "path": "/cache", "value": 64 },
+            { "op": "add", "path": "/data/-", "value": "data4" },
+            { "op": "remove", "path": "/data/0" },
+            { "op": "move", "from": "/cache", "path": "/limits" },
+            { "op": "copy", "from": "/data/0", "path": "/first" },
+            { "op": "replace", "path": "/first", "value": "data0" },
+        ])).unwrap();
+        t.patch_params("r1.s-2",&ParamsPatch::Json(ops)).unwrap();
+        assert_eq!(t.get("r1.s-2").unwrap().config.params(),Some(&json!({ "data": ["data3","data4"], "limits": 64, "first": "data0" })));
+
+        let prev = t.get("r1.s-2").unwrap().clone();
+        let ops: Vec<PatchOp> = serde_json::from_value(json!([
+            { "op": "add", "path": "/cache", "value": 1 },
+            { "op": "test", "path": "/limits", "value": 32 },
+        ])).unwrap();
+        let e = t.patch_params("r1.s-2",&ParamsPatch::Json(ops)).unwrap_err();
+        assert_eq!(e.kind(),ErrorKind::UnexpectedValue);
+        assert!(t.patch_params("r1.s-2",&ParamsPatch::Merge(json!([1,2]))).is_err());
+        assert_eq!(t.get("r1.s-2"),Some(&prev));
+
+        assert_eq!(t.patch_params("r1.s-9",&ParamsPatch::Merge(json!({}))).unwrap_err().kind(),ErrorKind::UnknownNode);
+    }
+
+    #[test]
+    fn canonical_json() {
+        let t: Topology = example().parse().unwrap();
+        let c = t.canonical_json();
+        assert!(c.starts_with("{\"hosts\":{\"r1\":{\"host\":\"r1.local\",\"platform\":null,\"port\":25000}"));
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&c).unwrap()["root"]["children"][0]["name"],"r1");
+
+        let json = serde_json::to_string(&toml::from_str::<toml::Value>(example()).unwrap()).unwrap();
+        assert_eq!(Topology::from_json_str(&json).unwrap().canonical_json(),c);
+
+        let params = |p: &str| {
+            let t: Topology = example().replace("params = { mode = \"s\", data = [ \"data2\", \"data3\"] }",p).parse().unwrap();
+            let v: serde_json::Value = serde_json::from_str(&t.canonical_json()).unwrap();
+            let mut res = String::new();
+            write_canonical(&v["root"]["children"][0]["children"][1]["config"]["params"],&mut res);
+            res
+        };
+        assert_eq!(params("params = { b = 1.0, a = -0.0, c = 0.25, d = 1e21 }"),"{\"a\":0,\"b\":1,\"c\":0.25,\"d\":1e+21}");
+        assert_eq!(params("params = { at = 1979-05-27T07:32:00.500+00:00, day = 1979-05-27, t = 07:32:00 }"),
+                   "{\"at\":\"1979-05-27T07:32:00.5Z\",\"day\":\"1979-05-27\",\"t\":\"07:32:00\"}");
+        assert_eq!(params("params = { at = 1979-05-27 07:32:00-07:30 }"),"{\"at\":\"1979-05-27T07:32:00-07:30\"}");
+    }
+
+    #[test]
+    fn typed_nodes() {
+        #[derive(Debug,Deserialize,PartialEq)]
+        struct Conf {
+            #[serde(default)]
+            mode: Option<String>,
+            #[serde(default)]
+            data: Vec<String>,
+        }
+
+        let t: Topology = example().parse().unwrap();
+        let c: Conf = t.get("r1.s-2").unwrap().config.params_as().unwrap();
+        assert_eq!(c,Conf { mode: Some("s".to_string()), data: vec!["data2".to_string(),"data3".to_string()] });
+
+        let nodes = t.typed_nodes::<Conf>().unwrap();
+        assert_eq!(nodes.len(),t.root.iter().filter(|n| matches!(n.config,RunConf::Active{ .. })).count());
+        assert_eq!(nodes.iter().find(|(n,_)| *n == "r2.s.s-2").map(|(_,c)| c.data.as_slice()),Some(&["data2".to_string()][..]));
+
+        #[derive(Debug,Deserialize)]
+        #[allow(dead_code)]
+        struct Strict {
+            mode: String,
+            limits: u32,
+        }
+        let e = t.typed_nodes::<Strict>().unwrap_err();
+        assert_eq!(e.kind(),ErrorKind::UnexpectedValue);
+        assert!(e.message().contains("missing field `limits`"),"{}",e.message());
+        assert!(e.to_string().starts_with(&format!("{}: invalid params: ",e.name())),"{}",e);
+    }
+
+    #[test]
+    fn topology_options() {
+        use serde_json::json;
+
+        let src = example().replace("params = { mode = \"s\", data = [ \"data2\", \"data3\"] }",
+                                    "params = { at = 1979-05-27T07:32:00Z, limits = [1.5, nan, -inf] }");
+        let (t,w) = Topology::from_str_with(&src,&TopologyOptions::default()).unwrap();
+        assert_eq!(t,src.parse().unwrap());
+        assert_eq!(t.get("r1.s-2").unwrap().config.params(),Some(&json!({ "at": "1979-05-27T07:32:00Z", "limits": [1.5,null,null] })));
+        assert_eq!(w.iter().map(|w| w.to_string()).collect::<Vec<_>>(),vec![
+            "r1.s-2 params '/limits/1': float NaN is converted to null",
+            "r1.s-2 params '/limits/2': float -inf is converted to null",
+        ]);
+
+        let options = TopologyOptions {
+            datetimes: DatetimeConversion::Tagged,
+            ..TopologyOptions::default()
+        };
+        let (t,_) = Topology::from_str_with(&src,&options).unwrap();
+        assert_eq!(t.get("r1.s-2").unwrap().config.param_at("/at"),Some(&json!({ "$datetime": "1979-05-27T07:32:00Z" })));
+
+        let options = TopologyOptions {
+            floats: FloatConversion::Error,
+            ..TopologyOptions::default()
+        };
+        match Topology::from_str_with(&src,&options) {
+            Err(LoadError::Invalid{ error, .. }) => assert!(error.message().contains("params '/limits/1'"),"{}",error),
+            r => panic!("unexpected: {:?}",r),
+        }
+        assert!(Topology::from_str_with(example(),&options).unwrap().1.is_empty());
+    }
+
+    #[test]
+    fn env_substitution() {
+        std::env::set_var("UNIVERSUM_TEST_R1","r1.prod.local");
+        std::env::set_var("UNIVERSUM_TEST_PORT","25300");
+        let src = example()
+            .replace("host = \"r1.local\"","host = \"${UNIVERSUM_TEST_R1}\"")
+            .replace("port = 25102 }","port = \"${UNIVERSUM_TEST_PORT}\" }")
+            .replace("mode = \"s\", data = [ \"data2\"","mode = \"$${s}\", data = [ \"${UNIVERSUM_TEST_R1}:2\"");
+        assert!(Topology::from_str_with(&src,&TopologyOptions::default()).is_err());
+
+        let options = TopologyOptions {
+            env_substitution: true,
+            ..TopologyOptions::default()
+        };
+        let (t,_) = Topology::from_str_with(&src,&options).unwrap();
+        assert_eq!(t.hosts["r1"].host,"r1.prod.local");
+        let c = &t.get("r1.s-2").unwrap().config;
+        assert_eq!(c.location().map(|l| l.port),Some(25300));
+        assert_eq!(c.param_as::<&str>("/mode").unwrap(),"${s}");
+        assert_eq!(c.param_as::<&str>("/data/0").unwrap(),"r1.prod.local:2");
+
+        // metadata is not substituted
+        let meta = src.clone() + "\n[config.r1.s-2.meta]\nnote = \"${UNIVERSUM_TEST_UNSET}\"\nparams = { at = \"${UNIVERSUM_TEST_UNSET}\" }\n";
+        let (t,_) = Topology::from_str_with(&meta,&options).unwrap();
+        assert_eq!(t.get("r1.s-2").unwrap().meta()["params"]["at"],"${UNIVERSUM_TEST_UNSET}");
+
+        let src = src.replace("UNIVERSUM_TEST_PORT","UNIVERSUM_TEST_UNSET");
+        match Topology::from_str_with(&src,&options) {
+            Err(LoadError::Parse{ error, .. }) => assert!(error.contains("config.r1.s-2.location.port"),"{}",error),
+            r => panic!("unexpected: {:?}",r),
+        }
+    }
+
+    #[test]
+    fn node_meta() {
+        use serde_json::json;
+
+        let src = example().replace("[config.r1.s-2]\n","[config.r1.s-2]\nx-owner = \"team-a\"\n")
+            + "\n[config.r1.s-2.meta]\ndashboard = { id = 42, panels = [ \"rps\" ] }\n";
+        let t: Topology = src.parse().unwrap();
+        let n = t.get("r1.s-2").unwrap();
+        assert_eq!(serde_json::Value::Object(n.meta().clone()),json!({ "x-owner": "team-a", "dashboard": { "id": 42, "panels": ["rps"] } }));
+        assert!(t.get("r1.s-2.meta").is_none());
+        assert!(t.get("r1").unwrap().meta().is_empty());
+
+        let sub = t.extract_subtree("r1").unwrap();
+        assert_eq!(sub.get("r1.s-2").unwrap().meta(),n.meta());
+        assert!(sub.canonical_json().contains("\"meta\":{\"dashboard\":{\"id\":42,\"panels\":[\"rps\"]},\"x-owner\":\"team-a\"}"));
+
+        let json = serde_json::to_string(&toml::from_str::<toml::Value>(&src).unwrap()).unwrap();
+        assert_eq!(Topology::from_json_str(&json).unwrap(),t);
+
+        let mut t = t;
+        for n in t.root.iter_mut() {
+            n.meta.remove("x-owner");
+        }
+        assert_eq!(t.get("r1.s-2").unwrap().meta().len(),1);
+
+        assert!(example().replace("[config.r1.s-2]\n","[config.r1.s-2]\nmeta = 1\n").parse::<Topology>().is_err());
+
+        // `x-` tables are child nodes, `meta` can't be a nested node
+        let src = example().replace("r1 = [\"d-a\", \"s-2\"]","r1 = [\"d-a\", \"s-2\", \"x-ray\"]")
+            + "\n[config.r1.x-ray]\nparams = {}\nlocation = { host = \"r1\", port = 25109 }\n";
+        let t: Topology = src.parse().unwrap();
+        assert!(t.get("r1.x-ray").is_some());
+        assert!(t.get("r1").unwrap().meta().is_empty());
+
+        let src = src.replace("x-ray","meta");
+        match src.parse::<Topology>() {
+            Err(LoadError::Invalid{ error, .. }) => {
+                assert_eq!(error.kind(),ErrorKind::InvalidName);
+                assert_eq!((error.parent(),error.name()),("r1","meta"));
+            },
+            r => panic!("unexpected: {:?}",r),
+        }
+
+        // nor a nested grouping
+        let src = example().replace("[root.r2]\n","[root.r2.meta]\nd = []\n\n[root.r2]\n");
+        match src.parse::<Topology>() {
+            Err(LoadError::Invalid{ error, .. }) => {
+                assert_eq!(error.kind(),ErrorKind::InvalidName);
+                assert_eq!((error.parent(),error.name()),("r2","meta"));
+            },
+            r => panic!("unexpected: {:?}",r),
+        }
+    }
 }